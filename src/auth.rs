@@ -0,0 +1,263 @@
+//! Generate the authentication of the client from the security schemes of the API.
+//!
+//! Every scheme becomes a constant of the generated `auth` module, e.g. `TOKEN_AUTH` for
+//! `tokenAuth`. Every operation passes the alternative requirements it accepts to the client,
+//! which sends its credentials for the first one they satisfy.
+
+use std::collections::HashMap;
+
+use convert_case::{Case, Casing};
+use openapiv3::{
+    APIKeyLocation, Components, OpenAPI, Operation, PathItem, ReferenceOr, SecurityScheme,
+};
+
+use crate::bindgen::make_comment;
+use crate::error::ThanixError;
+
+/// Generate the `auth` module of the client, with a constant for every security scheme.
+///
+/// # Parameters
+///
+/// * `components: Option<&Components>` - The components declaring the security schemes.
+///
+/// # Returns
+///
+/// * `Result<String, ThanixError>` - The code of the module, or an error pointing to a scheme
+///   which can't be used.
+pub fn generate(components: Option<&Components>) -> Result<String, ThanixError> {
+    let mut result = include_str!("templates/auth.rs.template").to_owned();
+    let mut names: HashMap<String, &str> = HashMap::new();
+    for (name, scheme) in components.into_iter().flat_map(|c| &c.security_schemes) {
+        let location = ["components", "securitySchemes", name.as_str()];
+        let scheme = match scheme {
+            ReferenceOr::Item(x) => x,
+            ReferenceOr::Reference { reference } => {
+                return Err(ThanixError::schema(
+                    &location,
+                    format!("`$ref` to `{}` couldn't be resolved", reference),
+                ));
+            }
+        };
+        let const_name = const_name(name);
+        if let Some(other) = names.insert(const_name.clone(), name) {
+            return Err(ThanixError::schema(
+                &location,
+                format!("has the same constant name `{}` as `{}`", const_name, other),
+            ));
+        }
+
+        let (description, value) = match scheme {
+            SecurityScheme::APIKey {
+                location,
+                name,
+                description,
+                ..
+            } => {
+                let location = match location {
+                    APIKeyLocation::Query => "Query",
+                    APIKeyLocation::Header => "Header",
+                    APIKeyLocation::Cookie => "Cookie",
+                };
+                (
+                    description,
+                    format!("ApiKey(ApiKeyLocation::{}, {:?})", location, name),
+                )
+            }
+            SecurityScheme::HTTP {
+                scheme,
+                description,
+                ..
+            } => (description, format!("Http({:?})", scheme.to_lowercase())),
+            SecurityScheme::OAuth2 { description, .. }
+            | SecurityScheme::OpenIDConnect { description, .. } => {
+                (description, "OAuth2".to_owned())
+            }
+        };
+        result += &format!(
+            "\n{}pub const {}: SecurityScheme = SecurityScheme::{};\n",
+            make_comment(
+                Some(description.clone().unwrap_or_else(|| format!("`{}`", name))),
+                0
+            ),
+            const_name,
+            value
+        );
+    }
+    Ok(result)
+}
+
+/// Get the name of the constant of a security scheme, e.g. `TOKEN_AUTH` for `tokenAuth`.
+pub fn const_name(scheme: &str) -> String {
+    let name = scheme
+        .replace(|c: char| !c.is_ascii_alphanumeric(), "_")
+        .to_case(Case::UpperSnake);
+    if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("_{}", name)
+    } else {
+        name
+    }
+}
+
+/// Give every operation of `item` without a `security` requirement of its own the one of the
+/// API, and check that all requirements name declared security schemes.
+///
+/// # Parameters
+///
+/// * `path: &str` - The path of the item, for errors.
+/// * `item: &mut PathItem` - The operations of the path.
+/// * `api: &OpenAPI` - The API declaring the security schemes and the default requirement.
+pub fn inherit_security(path: &str, item: &mut PathItem, api: &OpenAPI) -> Result<(), ThanixError> {
+    let schemes = api.components.as_ref().map(|c| &c.security_schemes);
+    let operations = [
+        (&mut item.get, "get"),
+        (&mut item.put, "put"),
+        (&mut item.post, "post"),
+        (&mut item.delete, "delete"),
+        (&mut item.options, "options"),
+        (&mut item.head, "head"),
+        (&mut item.patch, "patch"),
+        (&mut item.trace, "trace"),
+    ];
+    for (op, method) in operations {
+        let Some(op) = op else {
+            continue;
+        };
+        if op.security.is_none() {
+            op.security = api.security.clone();
+        }
+        for requirement in op.security.iter().flatten() {
+            if let Some(name) = requirement
+                .keys()
+                .find(|name| !schemes.is_some_and(|s| s.contains_key(*name)))
+            {
+                return Err(ThanixError::schema(
+                    &["paths", path, method, "security"],
+                    format!("security scheme `{}` isn't declared", name),
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Get the argument passed to the client for the `security` requirement of an operation, e.g.
+/// `Some(&[&[crate::auth::TOKEN_AUTH]])`.
+///
+/// `None` means the API doesn't declare any requirements, while an empty one is a requirement
+/// which doesn't need any credentials.
+pub fn security_arg(op: &Operation) -> String {
+    match &op.security {
+        Some(requirements) => format!(
+            "Some(&[{}])",
+            requirements
+                .iter()
+                .map(|requirement| format!(
+                    "&[{}]",
+                    requirement
+                        .keys()
+                        .map(|name| format!("crate::auth::{}", const_name(name)))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        None => "None".to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static SPEC: &str = "openapi: 3.0.3\n\
+        info: {title: Test, version: '1'}\n\
+        security: [{tokenAuth: []}]\n\
+        paths:\n\
+        \x20 /api/status/:\n\
+        \x20   get: {security: [], responses: {}}\n\
+        \x20 /api/dcim/devices/:\n\
+        \x20   get: {responses: {}}\n\
+        \x20   post: {security: [{cookieAuth: []}, {tokenAuth: []}, {}], responses: {}}\n\
+        components:\n\
+        \x20 securitySchemes:\n\
+        \x20   cookieAuth: {type: apiKey, in: cookie, name: sessionid}\n\
+        \x20   tokenAuth:\n\
+        \x20     type: apiKey\n\
+        \x20     in: header\n\
+        \x20     name: Authorization\n\
+        \x20     description: Token-based authentication with required prefix \"Token\"\n\
+        \x20   basic: {type: http, scheme: Basic}\n\
+        \x20   oidc: {type: openIdConnect, openIdConnectUrl: 'https://example.com'}\n";
+
+    fn item(api: &OpenAPI, path: &str) -> PathItem {
+        api.paths.paths[path].as_item().unwrap().clone()
+    }
+
+    #[test]
+    fn test_generate() {
+        let api: OpenAPI = serde_yaml::from_str(SPEC).unwrap();
+        let result = generate(api.components.as_ref()).unwrap();
+        assert!(result.contains(
+            "/// `cookieAuth`\npub const COOKIE_AUTH: SecurityScheme = SecurityScheme::ApiKey(ApiKeyLocation::Cookie, \"sessionid\");\n"
+        ));
+        assert!(result.contains(
+            "/// Token-based authentication with required prefix \"Token\"\npub const TOKEN_AUTH: SecurityScheme = SecurityScheme::ApiKey(ApiKeyLocation::Header, \"Authorization\");\n"
+        ));
+        assert!(
+            result.contains("pub const BASIC: SecurityScheme = SecurityScheme::Http(\"basic\");\n")
+        );
+        assert!(result.contains("pub const OIDC: SecurityScheme = SecurityScheme::OAuth2;\n"));
+    }
+
+    #[test]
+    fn test_generate_same_name() {
+        let components: Components = serde_yaml::from_str(
+            "securitySchemes:\n\
+             \x20 tokenAuth: {type: http, scheme: bearer}\n\
+             \x20 token_auth: {type: http, scheme: bearer}\n",
+        )
+        .unwrap();
+        assert!(generate(Some(&components)).is_err());
+    }
+
+    #[test]
+    fn test_inherit_security() {
+        let api: OpenAPI = serde_yaml::from_str(SPEC).unwrap();
+
+        let mut status = item(&api, "/api/status/");
+        inherit_security("/api/status/", &mut status, &api).unwrap();
+        assert_eq!(security_arg(status.get.as_ref().unwrap()), "Some(&[])");
+
+        let mut devices = item(&api, "/api/dcim/devices/");
+        inherit_security("/api/dcim/devices/", &mut devices, &api).unwrap();
+        assert_eq!(
+            security_arg(devices.get.as_ref().unwrap()),
+            "Some(&[&[crate::auth::TOKEN_AUTH]])"
+        );
+        assert_eq!(
+            security_arg(devices.post.as_ref().unwrap()),
+            "Some(&[&[crate::auth::COOKIE_AUTH], &[crate::auth::TOKEN_AUTH], &[]])"
+        );
+        assert_eq!(security_arg(&Operation::default()), "None");
+    }
+
+    #[test]
+    fn test_inherit_security_unknown() {
+        let mut api: OpenAPI = serde_yaml::from_str(SPEC).unwrap();
+        api.security = serde_yaml::from_str("[{apiKey: []}]").unwrap();
+        let mut devices = item(&api, "/api/dcim/devices/");
+        let err = inherit_security("/api/dcim/devices/", &mut devices, &api).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "#/paths/~1api~1dcim~1devices~1/get/security: security scheme `apiKey` isn't declared"
+        );
+    }
+
+    #[test]
+    fn test_const_name() {
+        assert_eq!(const_name("tokenAuth"), "TOKEN_AUTH");
+        assert_eq!(const_name("api-key"), "API_KEY");
+        assert_eq!(const_name("2fa"), "_2_FA");
+    }
+}
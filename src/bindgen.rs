@@ -1,118 +1,794 @@
 //! Generate bindings, parse YAML and create output files.
 
+use crate::auth;
+use crate::config::GeneratorConfig;
+use crate::error::ThanixError;
+use crate::filter::{self, OperationFilter};
 use crate::pathgen;
+use crate::split;
 use crate::structgen;
+use crate::util::{UNSANITARY_OBJECTS, UnsanitaryPattern};
+use crate::{bundle, openapi31, resolve, swagger2};
+use convert_case::{Case, Casing};
+use openapiv3::AdditionalProperties;
+use openapiv3::ObjectType;
 use openapiv3::Schema;
 use openapiv3::SchemaKind;
 use openapiv3::Type;
-use openapiv3::{OpenAPI, ReferenceOr};
+use openapiv3::{Components, OpenAPI, ReferenceOr};
+use openapiv3::{IntegerFormat, IntegerType, NumberFormat, StringFormat, VariantOrUnknownOrEmpty};
 use std::{
-    fs::{self, File},
+    collections::{BTreeMap, HashMap, HashSet},
+    fs,
     io::{self, Write},
-    path::Path,
+    path::{Path, PathBuf},
 };
 
-/// Generate Rust bindings from an OpenAPI schema.
-pub fn generate(
-    input_path: impl AsRef<Path>,
-    output_path: impl AsRef<Path>,
-    workaround_mode: bool,
-) {
-    // Parse the schema.
-    let input = fs::read_to_string(input_path).unwrap();
-    let api: OpenAPI = serde_yaml::from_str(&input).unwrap();
-
-    // Populate the output directory.
-    let output_path = output_path.as_ref();
-    create_lib_dir(output_path).unwrap();
-
-    // Create and open the output file for structs.
-    let mut types_file = File::create(output_path.join("src/").join("types.rs")).unwrap();
-    // TODO: We don't really need these.
-    //write!(types_file, "{}", include_str!("templates/usings.template")).unwrap();
+/// Rust types used for the `format` of a string, number or integer schema.
+///
+/// The third element is whether the type implements `Default`, which structs with a required field
+/// of the type can only derive if it does.
+/// Every entry can be overridden with `--type-mapping`.
+static FORMAT_TYPES: &[(&str, &str, bool)] = &[
+    ("date-time", "chrono::DateTime<chrono::Utc>", true),
+    ("date", "chrono::NaiveDate", true),
+    ("uuid", "uuid::Uuid", true),
+    ("uri", "url::Url", false),
+    ("ipv4", "std::net::IpAddr", false),
+    ("ipv6", "std::net::IpAddr", false),
+    // Only `byte` is base64, `binary` is raw octets and stays a string until binary bodies are
+    // supported.
+    ("byte", "crate::util::Base64", true),
+    ("float", "f32", true),
+];
 
-    // For every component.
-    for (name, schema) in &api.components.unwrap().schemas {
-        let s = match schema {
-            ReferenceOr::Item(x) => x,
-            _ => continue,
+/// State shared by all types generated during a single run.
+///
+/// Inline schemas that need a type of their own (e.g. enumerated strings) are hoisted out of
+/// their parent and collected here, until they are written to the types module.
+#[derive(Debug, Default)]
+pub struct TypeContext {
+    /// The components of the schema, to resolve references with.
+    pub components: Components,
+    /// Whether the `--workaround` flag has been set.
+    pub workaround_mode: bool,
+    /// What gets loosened in workaround mode.
+    pub unsanitary: Vec<UnsanitaryPattern>,
+    /// Whether generated enums get a `#[serde(other)] Unknown` catch-all variant.
+    pub enum_fallback: bool,
+    /// Rust types for schema formats, taking precedence over [`FORMAT_TYPES`].
+    pub type_mappings: HashMap<String, String>,
+    /// Whether maps are generated as `BTreeMap` instead of `HashMap`.
+    pub btree_maps: bool,
+    /// Definitions of hoisted types which haven't been written yet.
+    pub hoisted: Vec<String>,
+    /// Problems with the schema which didn't stop the generation, e.g. skipped members.
+    pub warnings: Vec<String>,
+    /// All type names which are already taken.
+    names: HashSet<String>,
+    /// Names of already hoisted schemas, keyed by their name hint and serialized schema.
+    hoisted_names: HashMap<(String, String), String>,
+    /// Whether the type of a component implements `Default`, keyed by component name.
+    defaults: HashMap<String, bool>,
+}
+
+impl TypeContext {
+    /// Mark a type name as taken, e.g. by a component schema.
+    pub fn reserve(&mut self, name: &str) {
+        self.names.insert(name.to_owned());
+    }
+
+    /// Get the Rust type for a schema `format`, if there is a mapping for it.
+    pub fn format_type(&self, format: &str) -> Option<String> {
+        self.type_mappings.get(format).cloned().or_else(|| {
+            FORMAT_TYPES
+                .iter()
+                .find(|(name, _, _)| *name == format)
+                .map(|(_, typ, _)| typ.to_string())
+        })
+    }
+
+    /// Check whether the type generated for `schema` implements `Default`.
+    ///
+    /// Unions don't, as none of their variants is a sensible default, and neither do structs with
+    /// a required field of such a type. Optional and nullable fields are `Option`s, which always
+    /// do.
+    ///
+    /// # Parameters
+    ///
+    /// * `schema: &ReferenceOr<Schema>` - The schema of the type.
+    /// * `hint: &str` - The name the type is hoisted under, if it's an inline schema.
+    pub fn implements_default(&mut self, schema: &ReferenceOr<Schema>, hint: &str) -> bool {
+        let item = match schema {
+            ReferenceOr::Reference { reference } => {
+                let name = reference.replace("#/components/schemas/", "");
+                if let Some(result) = self.defaults.get(&name) {
+                    return *result;
+                }
+                // Assume it does while looking into it, recursive types need an `Option` anyway.
+                self.defaults.insert(name.clone(), true);
+                let result = match self.components.schemas.get(&name).cloned() {
+                    Some(ReferenceOr::Item(item)) => self.struct_implements_default(&name, &item),
+                    Some(component) => self.implements_default(&component, &name),
+                    None => true,
+                };
+                self.defaults.insert(name, result);
+                return result;
+            }
+            ReferenceOr::Item(item) => item,
+        };
+        if item.schema_data.nullable {
+            return true;
+        }
+        match &item.schema_kind {
+            // Enums only implement it if the schema has a default.
+            SchemaKind::Type(Type::String(x)) if x.enumeration.iter().any(Option::is_some) => {
+                structgen::enum_default(item).is_some()
+            }
+            // Types of `--type-mapping` are assumed to implement it, unless they are one of ours
+            // which doesn't.
+            SchemaKind::Type(t @ (Type::String(_) | Type::Number(_) | Type::Integer(_))) => {
+                format_of(t)
+                    .and_then(|f| self.format_type(&f))
+                    .is_none_or(|typ| {
+                        !FORMAT_TYPES
+                            .iter()
+                            .any(|(_, name, default)| *name == typ && !default)
+                    })
+            }
+            SchemaKind::AllOf { all_of: members }
+            | SchemaKind::OneOf { one_of: members }
+            | SchemaKind::AnyOf { any_of: members }
+                if members.len() == 1 =>
+            {
+                self.implements_default(&members[0], hint)
+            }
+            _ => self.struct_implements_default(hint, item),
+        }
+    }
+
+    /// Check whether the type [`structgen::generate`] generates for `schema` implements `Default`,
+    /// see [`implements_default`](Self::implements_default).
+    pub fn struct_implements_default(&mut self, name: &str, schema: &Schema) -> bool {
+        let mut flattened = Vec::new();
+        let merged;
+        let obj = match &schema.schema_kind {
+            SchemaKind::Type(Type::Object(x)) => x,
+            SchemaKind::AllOf { all_of } if !all_of.is_empty() => {
+                let mut obj = ObjectType::default();
+                structgen::merge_all_of(
+                    all_of,
+                    self,
+                    &mut obj,
+                    &mut flattened,
+                    &mut Vec::new(),
+                    &mut Vec::new(),
+                );
+                merged = obj;
+                &merged
+            }
+            SchemaKind::OneOf { one_of: members } | SchemaKind::AnyOf { any_of: members } => {
+                return members.is_empty();
+            }
+            SchemaKind::Type(Type::String(x)) if x.enumeration.iter().any(Option::is_some) => {
+                return structgen::enum_default(schema).is_some();
+            }
+            // Arrays become a struct around their item type.
+            SchemaKind::Type(Type::Array(x)) => {
+                return x.items.as_ref().is_none_or(|items| {
+                    self.implements_default(&items.clone().unbox(), &format!("{}Item", name))
+                });
+            }
+            _ => return true,
         };
-        // Generate struct and write it to file.
-        if let Some(structure) = structgen::generate(name, s, workaround_mode) {
-            types_file.write_all(structure.as_bytes()).unwrap();
+        let required = obj
+            .properties
+            .iter()
+            // Patch requests only have optional fields.
+            .filter(|(prop_name, _)| {
+                obj.required.contains(prop_name) && !name.starts_with("Patched")
+            })
+            .map(|(prop_name, prop)| {
+                (
+                    prop.clone().unbox(),
+                    format!("{}{}", name, prop_name.to_case(Case::Pascal)),
+                )
+            })
+            .collect::<Vec<_>>();
+        required
+            .iter()
+            .all(|(prop, hint)| self.implements_default(prop, hint))
+            && flattened.iter().enumerate().all(|(i, member)| {
+                self.implements_default(member, &format!("{}AllOf{}", name, i + 1))
+            })
+    }
+
+    /// Get the map type for the given value type.
+    pub fn map_type(&self, value: &str) -> String {
+        let map = if self.btree_maps {
+            "BTreeMap"
         } else {
-            eprintln!("Structure {} couldn't be generated", name);
+            "HashMap"
+        };
+        format!("std::collections::{}<String, {}>", map, value)
+    }
+
+    /// Hoist an inline schema into a named type.
+    ///
+    /// The name is derived from `hint` and made unique by appending a number. Hoisting the same
+    /// schema under the same hint again returns the existing name instead of a duplicate type.
+    ///
+    /// # Parameters
+    ///
+    /// * `hint: &str` - The preferred name of the new type.
+    /// * `schema: &Schema` - The inline schema to hoist.
+    /// * `define` - Generates the type definition for the final name.
+    ///
+    /// # Returns
+    ///
+    /// * `String` - The name of the hoisted type.
+    pub fn hoist(
+        &mut self,
+        hint: &str,
+        schema: &Schema,
+        define: impl FnOnce(&mut Self, &str) -> String,
+    ) -> String {
+        let hint = if hint.is_empty() { "Inline" } else { hint };
+        let key = (
+            hint.to_owned(),
+            serde_yaml::to_string(schema).unwrap_or_default(),
+        );
+        if let Some(name) = self.hoisted_names.get(&key) {
+            return name.clone();
+        }
+
+        let mut name = hint.to_owned();
+        let mut counter = 1;
+        while self.names.contains(&name) {
+            counter += 1;
+            name = format!("{}{}", hint, counter);
+        }
+        self.reserve(&name);
+        self.hoisted_names.insert(key, name.clone());
+
+        let definition = define(self, &name);
+        self.hoisted.push(definition);
+        name
+    }
+}
+
+/// Name of the generated crate if none is configured.
+pub const DEFAULT_CRATE_NAME: &str = "thanix_client";
+
+/// Generates an API client crate from an OpenAPI schema.
+#[derive(Debug, Clone, Default)]
+pub struct Generator {
+    config: GeneratorConfig,
+}
+
+/// The files of a generated crate, keyed by their path relative to the crate root.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GeneratedCrate {
+    /// Name of the crate, as written to its `Cargo.toml`.
+    pub name: String,
+    pub files: BTreeMap<PathBuf, String>,
+    /// Things which were skipped during generation, e.g. components we can't make a type of.
+    pub warnings: Vec<String>,
+}
+
+impl Generator {
+    /// Create a generator with the given configuration.
+    pub fn new(config: GeneratorConfig) -> Self {
+        Self { config }
+    }
+
+    /// The configuration of this generator.
+    pub fn config(&self) -> &GeneratorConfig {
+        &self.config
+    }
+
+    /// Parse the OpenAPI schema at `input_path` and generate a crate from it.
+    pub fn generate_from_file(
+        &self,
+        input_path: impl AsRef<Path>,
+    ) -> Result<GeneratedCrate, ThanixError> {
+        self.generate(&read_spec(input_path)?)
+    }
+
+    /// Generate Rust bindings from an OpenAPI schema.
+    ///
+    /// All local `$ref`s but the ones to component schemas are resolved first, see
+    /// [`resolve_refs`](crate::resolve_refs).
+    ///
+    /// The operations are split into modules by their first tag, like `src/paths/dcim.rs`, and
+    /// the types into the modules of the operations using them. `lib.rs` re-exports the contents
+    /// of all modules, so they are still available as `paths::*` and `types::*`.
+    ///
+    /// If `async_mode` is set, an `async_paths` module with `async fn` operations over
+    /// `reqwest::Client` is generated next to the blocking `paths` module.
+    pub fn generate(&self, api: &OpenAPI) -> Result<GeneratedCrate, ThanixError> {
+        let config = &self.config;
+        // The generators only look at component schemas through references.
+        let api = &resolve::resolve_refs(api)?;
+        let mut output = GeneratedCrate {
+            name: self.crate_name(),
+            ..Default::default()
+        };
+
+        let filter = OperationFilter::new(&config.filter)?;
+        let mut items = Vec::new();
+        for (name, path) in &api.paths.paths {
+            let p = match path {
+                ReferenceOr::Item(x) => x,
+                ReferenceOr::Reference { reference } => {
+                    return Err(pathgen::unresolved(reference, &[name]));
+                }
+            };
+            if let Some(mut item) = filter.path_item(name, p) {
+                auth::inherit_security(name, &mut item, api)?;
+                items.push((name.as_str(), item));
+            }
+        }
+
+        // A schema without components is valid, it just doesn't get any types.
+        let mut components = api.components.clone().unwrap_or_default();
+        if !config.filter.is_empty() {
+            filter::prune_schemas(&mut components, items.iter().map(|(_, item)| item));
+        }
+        let mut ctx = self.type_context(components)?;
+
+        let modules = split::group(&items);
+        let layout = split::layout(&ctx.components, &modules);
+        // Every module can be enabled on its own, together with the ones whose types it uses.
+        let features: BTreeMap<String, Vec<String>> = layout
+            .dependencies
+            .iter()
+            .map(|(module, dependencies)| {
+                let dependencies = dependencies
+                    .iter()
+                    .map(|d| split::feature_name(d))
+                    .collect();
+                (split::feature_name(module), dependencies)
+            })
+            .collect();
+        output.files = create_lib_files(config, &features);
+        output.files.insert(
+            "src/auth.rs".into(),
+            auth::generate(api.components.as_ref())?,
+        );
+
+        // The code of every types module, keyed by module name.
+        let mut types: BTreeMap<String, String> = BTreeMap::new();
+        for (name, code) in generate_components(&mut ctx) {
+            let module = layout
+                .owners
+                .get(&name)
+                .map_or(split::SHARED_MODULE, String::as_str);
+            *types.entry(module.to_owned()).or_default() += &code;
+        }
+
+        // The clients of the modules must not be shadowed by hoisted types either.
+        for module in modules.keys() {
+            ctx.reserve(&pathgen::client_name(module));
+        }
+
+        // For every module.
+        for (module, items) in &modules {
+            let mut paths = format!(
+                include_str!("templates/usings.template"),
+                "ThanixClient", "blocking::Response"
+            );
+            // The async operations live in their own module, as they need a different client and response type.
+            let mut async_paths = format!(
+                include_str!("templates/usings.template"),
+                "AsyncThanixClient as ThanixClient", "Response"
+            );
+
+            let mut signatures = Vec::new();
+            let mut async_signatures = Vec::new();
+
+            // For every path.
+            for (name, p) in items {
+                // Generate paths.
+                paths += &pathgen::generate(name, p, false, &mut ctx, &mut signatures)?;
+                if config.async_mode {
+                    async_paths +=
+                        &pathgen::generate(name, p, true, &mut ctx, &mut async_signatures)?;
+                }
+            }
+            paths += &pathgen::gen_client(module, &signatures, false);
+            if config.async_mode {
+                async_paths += &pathgen::gen_client(module, &async_signatures, true);
+            }
+
+            output
+                .files
+                .insert(format!("src/paths/{}.rs", module).into(), paths);
+            if config.async_mode {
+                output
+                    .files
+                    .insert(format!("src/async_paths/{}.rs", module).into(), async_paths);
+            }
+            // Types hoisted out of the operations belong to their module.
+            let hoisted = ctx.hoisted.drain(..).collect::<String>();
+            if !hoisted.is_empty() {
+                *types.entry(module.clone()).or_default() += &hoisted;
+            }
+        }
+
+        for (module, code) in &types {
+            output.files.insert(
+                format!("src/types/{}.rs", module).into(),
+                // Types can use the ones of all other modules.
+                format!("#[allow(unused_imports)]\nuse super::*;\n\n{}", code),
+            );
         }
+        output.files.insert(
+            "src/lib.rs".into(),
+            create_lib_rs(config, modules.keys(), types.keys()),
+        );
+        output.warnings = ctx.warnings;
+        Ok(output)
+    }
+
+    /// Generate a crate with only the types of `components`, without any client code.
+    ///
+    /// Used for plain JSON Schema documents, see [`read_json_schemas`](crate::read_json_schemas).
+    pub fn generate_types(&self, components: &Components) -> Result<GeneratedCrate, ThanixError> {
+        let mut output = GeneratedCrate {
+            name: self.crate_name(),
+            files: create_types_lib_files(&self.config),
+            ..Default::default()
+        };
+        let mut ctx = self.type_context(components.clone())?;
+        let types: String = generate_components(&mut ctx)
+            .into_iter()
+            .map(|(_, code)| code)
+            .collect();
+        output.files.insert(
+            "src/types.rs".into(),
+            format!("use serde::{{Deserialize, Serialize}};\n\n{}", types),
+        );
+        output.warnings = ctx.warnings;
+        Ok(output)
+    }
+
+    /// Generate a single `types.rs` module with the types of `components`, to be included in
+    /// another crate.
+    ///
+    /// The module only depends on `serde` and the crates of the formats it uses, e.g. `chrono`.
+    /// The helpers it needs are defined in a `util` module within it.
+    pub fn generate_types_module(
+        &self,
+        components: &Components,
+    ) -> Result<GeneratedCrate, ThanixError> {
+        let mut output = self.generate_types(components)?;
+        let types = output
+            .files
+            .remove(Path::new("src/types.rs"))
+            .unwrap_or_default();
+        // The helpers live next to the types, instead of in the `util` module of the crate.
+        let module = format!(
+            "{}\npub mod util {{\n{}}}\n",
+            types.replace("crate::util::", "self::util::"),
+            include_str!("templates/util.rs.types.template")
+        );
+        output.files = BTreeMap::from([("types.rs".into(), module)]);
+        Ok(output)
     }
 
-    // Create and open the output file for paths.
-    let mut paths_file = File::create(output_path.join("src/").join("paths.rs")).unwrap();
-    write!(paths_file, "{}", include_str!("templates/usings.template")).unwrap();
+    /// Name of the generated crate.
+    fn crate_name(&self) -> String {
+        self.config
+            .package
+            .name
+            .clone()
+            .unwrap_or_else(|| DEFAULT_CRATE_NAME.to_owned())
+    }
 
-    // For every path.
-    for (name, path) in &api.paths.paths {
-        let p = match path {
-            ReferenceOr::Item(x) => x,
-            _ => continue,
+    /// Create the state for generating the types of `components`.
+    fn type_context(&self, components: Components) -> Result<TypeContext, ThanixError> {
+        let config = &self.config;
+        let mut ctx = TypeContext {
+            components,
+            workaround_mode: config.workaround_mode,
+            unsanitary: unsanitary_patterns(&config.unsanitary)?,
+            enum_fallback: config.enum_fallback,
+            type_mappings: config.type_mappings.clone(),
+            btree_maps: config.btree_maps,
+            ..Default::default()
         };
-        // Generate paths and write to file.
-        if let Some(paths) = pathgen::generate(name, p) {
-            paths_file.write_all(paths.as_bytes()).unwrap();
+        // Hoisted types must not shadow any component.
+        let names: Vec<String> = ctx.components.schemas.keys().cloned().collect();
+        for name in &names {
+            ctx.reserve(name);
         }
+        Ok(ctx)
     }
 }
 
-/// Create all necessary structures and directories for the crate.
-/// Requires `output` folder to exist.
+/// Generate the types of all component schemas in `ctx`, including the ones hoisted out of them.
 ///
-/// # Arguments
+/// Schemas which can't be generated are reported in `ctx.warnings`.
+///
+/// # Returns
 ///
-/// - `output_name: &Path` - The name of the output library given by the CLI. Default `output`.
-fn create_lib_dir(output_path: &Path) -> io::Result<()> {
-    println!("Starting repackaging into crate...");
+/// * `Vec<(String, String)>` - The name of every component schema and the code of its types.
+fn generate_components(ctx: &mut TypeContext) -> Vec<(String, String)> {
+    let mut result = Vec::new();
+    let schemas = ctx.components.schemas.clone();
 
-    // Create the output folder.
-    _ = fs::create_dir(output_path);
+    // For every component.
+    for (name, schema) in &schemas {
+        let mut types = String::new();
+        match schema {
+            // A component which just references another one is an alias of it.
+            ReferenceOr::Reference { .. } => {
+                types += &format!(
+                    "pub type {} = {};\n\n",
+                    name,
+                    type_to_string(schema, name, ctx)
+                );
+            }
+            // Generate struct and write it to file.
+            ReferenceOr::Item(s) => match structgen::generate(name, s, ctx) {
+                Some(structure) => types += &structure,
+                None => ctx
+                    .warnings
+                    .push(format!("Structure {} couldn't be generated", name)),
+            },
+        }
+        types += &ctx.hoisted.drain(..).collect::<String>();
+        result.push((name.clone(), types));
+    }
+    result
+}
+
+impl GeneratedCrate {
+    /// Write the crate as an uncompressed tarball, with all files in a directory named after it.
+    pub fn write_tar(&self, writer: impl Write) -> io::Result<()> {
+        let mut builder = tar::Builder::new(writer);
+        for (name, contents) in &self.files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(
+                &mut header,
+                Path::new(&self.name).join(name),
+                contents.as_bytes(),
+            )?;
+        }
+        builder.into_inner()?.flush()
+    }
 
-    // Create the "src" subdirectory.
-    let src_dir = output_path.join("src/");
-    fs::create_dir_all(&src_dir)?;
+    /// Concatenate all files of the crate into a single text, each one starting with a
+    /// `==> path <==` line.
+    pub fn to_bundle(&self) -> String {
+        let mut result = String::new();
+        for (name, contents) in &self.files {
+            result += &format!("==> {} <==\n", name.display());
+            result += contents;
+            if !contents.ends_with('\n') {
+                result += "\n";
+            }
+        }
+        result
+    }
+
+    /// Write all files of the crate into the `output_path` directory, creating it if necessary.
+    ///
+    /// The `src` directory is removed first, so modules of a previous run, e.g. ones dropped by a
+    /// filter or from an older layout, don't end up in the crate.
+    pub fn write(&self, output_path: impl AsRef<Path>) -> Result<(), ThanixError> {
+        let output_path = output_path.as_ref();
+        let src = output_path.join("src");
+        if src.is_dir() && self.files.keys().any(|name| name.starts_with("src")) {
+            fs::remove_dir_all(&src).map_err(|source| ThanixError::Io { path: src, source })?;
+        }
+        for (name, contents) in &self.files {
+            let path = output_path.join(name);
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).map_err(|source| ThanixError::Io {
+                    path: parent.to_owned(),
+                    source,
+                })?;
+            }
+            fs::write(&path, contents).map_err(|source| ThanixError::Io { path, source })?;
+        }
+        Ok(())
+    }
+}
+
+/// Read and parse the OpenAPI schema at `input_path`, or from stdin if it is `-`.
+///
+/// The schema may be written in YAML or JSON, as OpenAPI 3.0 or 3.1. Files referenced by it are bundled into it, and all
+/// `$ref`s but the ones to component schemas are resolved.
+pub fn read_spec(input_path: impl AsRef<Path>) -> Result<OpenAPI, ThanixError> {
+    let input_path = input_path.as_ref();
+    let mut doc = bundle::read_document(input_path)?;
+    if swagger2::is_swagger_2(&doc) {
+        doc = swagger2::upgrade(&doc)?;
+    }
+    let mut doc = bundle::bundle(doc, input_path)?;
+    if openapi31::is_openapi_31(&doc) {
+        openapi31::downgrade(&mut doc)?;
+    }
+    serde_yaml::from_value(resolve::resolve_document(doc)?).map_err(|source| ThanixError::Parse {
+        path: input_path.to_owned(),
+        source,
+    })
+}
+
+/// Compile the configured `--unsanitary` patterns, falling back to [`UNSANITARY_OBJECTS`].
+fn unsanitary_patterns(patterns: &[String]) -> Result<Vec<UnsanitaryPattern>, ThanixError> {
+    let patterns: Vec<&str> = if patterns.is_empty() {
+        UNSANITARY_OBJECTS.to_vec()
+    } else {
+        patterns.iter().map(String::as_str).collect()
+    };
+    patterns
+        .into_iter()
+        .map(|pattern| {
+            UnsanitaryPattern::new(pattern).map_err(|source| ThanixError::Pattern {
+                pattern: pattern.to_owned(),
+                source,
+            })
+        })
+        .collect()
+}
+
+/// Create all static files needed for the crate.
+///
+/// # Arguments
+///
+/// - `config: &GeneratorConfig` - Package metadata and default features of the crate.
+/// - `features: &BTreeMap<String, Vec<String>>` - The feature of every module, with the features
+///   it depends on.
+fn create_lib_files(
+    config: &GeneratorConfig,
+    features: &BTreeMap<String, Vec<String>>,
+) -> BTreeMap<PathBuf, String> {
+    let mut files = BTreeMap::new();
+    let package = &config.package;
 
     // Create the "src/util.rs" file.
-    fs::write(
-        &src_dir.join("util.rs"),
-        include_str!("templates/util.rs.template"),
-    )?;
-
-    // Create the "src/lib.rs" file.
-    fs::write(
-        &src_dir.join("lib.rs"),
-        include_str!("templates/lib.rs.template"),
-    )?;
+    files.insert(
+        "src/util.rs".into(),
+        format!(
+            "{}\n{}",
+            include_str!("templates/util.rs.template"),
+            include_str!("templates/util.rs.types.template")
+        ),
+    );
+
+    // All modules are enabled by default, unless the crate is used with `default-features = false`.
+    let mut default_features = config
+        .default_features
+        .clone()
+        .unwrap_or_else(|| vec!["blocking".to_owned()]);
+    if !default_features.iter().any(|f| f == "full") {
+        default_features.push("full".to_owned());
+    }
 
     // Create the "Cargo.toml" file.
-    let mut cargo_file = fs::File::create(output_path.join("Cargo.toml"))?;
-    write!(
-        cargo_file,
-        include_str!("templates/Cargo.toml.template"),
-        // In case the user provides a relative path, use the last directory
-        // as the crate name.
-        output_path.file_name().unwrap().to_string_lossy()
-    )?;
-
-    fs::write(
-        output_path.join("build.rs"),
-        include_str!("templates/build.rs.template"),
-    )?;
+    files.insert(
+        "Cargo.toml".into(),
+        format!(
+            include_str!("templates/Cargo.toml.template"),
+            name = toml_value(package.name.as_deref().unwrap_or(DEFAULT_CRATE_NAME)),
+            version = toml_value(package.version.as_str()),
+            authors = toml_value(package.authors.clone()),
+            description = toml_value(package.description.as_str()),
+            license = toml_value(package.license.as_str()),
+            default_features = toml_value(default_features),
+            full = toml_value(features.keys().cloned().collect::<Vec<_>>()),
+            module_features = features
+                .iter()
+                .map(|(feature, dependencies)| format!(
+                    "{} = {}\n",
+                    feature,
+                    toml_value(dependencies.clone())
+                ))
+                .collect::<String>(),
+        ),
+    );
+
+    files.insert(
+        "build.rs".into(),
+        include_str!("templates/build.rs.template").to_owned(),
+    );
 
     // Create the "README.md" file.
-    fs::write(
-        output_path.join("README.md"),
-        include_str!("templates/README.md.template"),
-    )?;
+    files.insert(
+        "README.md".into(),
+        include_str!("templates/README.md.template").to_owned(),
+    );
+
+    files
+}
+
+/// Create the `lib.rs` file, which declares the modules of all tags and re-exports their contents.
+///
+/// # Arguments
+///
+/// - `config: &GeneratorConfig` - Whether the `async_paths` module should be declared.
+/// - `paths` - The modules with operations.
+/// - `types` - The modules with types, which can include ones without operations.
+fn create_lib_rs<'a>(
+    config: &GeneratorConfig,
+    paths: impl IntoIterator<Item = &'a String>,
+    types: impl IntoIterator<Item = &'a String>,
+) -> String {
+    let declare = |modules: Vec<&String>| -> String {
+        modules
+            .into_iter()
+            .map(|module| {
+                // Only the shared types are always available.
+                let cfg = if module == split::SHARED_MODULE {
+                    String::new()
+                } else {
+                    format!("\t#[cfg(feature = \"{}\")]\n", split::feature_name(module))
+                };
+                format!("{1}\tpub mod {0};\n{1}\tpub use {0}::*;\n", module, cfg)
+            })
+            .collect()
+    };
+    let paths = declare(paths.into_iter().collect());
+    format!(
+        include_str!("templates/lib.rs.template"),
+        paths = paths,
+        types = declare(types.into_iter().collect()),
+        async_paths = if config.async_mode {
+            format!(
+                "#[cfg(feature = \"async\")]\npub mod async_paths {{\n{}}}\n",
+                paths
+            )
+        } else {
+            String::new()
+        },
+    )
+}
+
+/// Create all static files needed for a crate which only contains types.
+///
+/// # Arguments
+///
+/// - `config: &GeneratorConfig` - Package metadata of the crate.
+fn create_types_lib_files(config: &GeneratorConfig) -> BTreeMap<PathBuf, String> {
+    let mut files = BTreeMap::new();
+    let package = &config.package;
 
-    println!("Output successfully repackaged!");
-    Ok(())
+    files.insert(
+        "src/util.rs".into(),
+        include_str!("templates/util.rs.types.template").to_owned(),
+    );
+    files.insert(
+        "src/lib.rs".into(),
+        include_str!("templates/lib.rs.types.template").to_owned(),
+    );
+    files.insert(
+        "Cargo.toml".into(),
+        format!(
+            include_str!("templates/Cargo.toml.types.template"),
+            name = toml_value(package.name.as_deref().unwrap_or(DEFAULT_CRATE_NAME)),
+            version = toml_value(package.version.as_str()),
+            authors = toml_value(package.authors.clone()),
+            description = toml_value(package.description.as_str()),
+            license = toml_value(package.license.as_str()),
+        ),
+    );
+    files.insert(
+        "README.md".into(),
+        include_str!("templates/README.md.template").to_owned(),
+    );
+
+    files
+}
+
+/// Format `value` for a TOML file, e.g. as a quoted and escaped string.
+fn toml_value(value: impl Into<toml::Value>) -> String {
+    value.into().to_string()
 }
 
 /// Makes a comment out of a given string.
@@ -127,60 +803,86 @@ pub fn make_comment(input: Option<String>, indent: usize) -> String {
     }
 }
 
-pub fn type_to_string(ty: &ReferenceOr<Schema>) -> String {
+/// Get the Rust type of a schema.
+///
+/// Inline schemas which need their own type definition are hoisted into `ctx`, named after
+/// `hint`.
+pub fn type_to_string(ty: &ReferenceOr<Schema>, hint: &str, ctx: &mut TypeContext) -> String {
     match ty {
         // If the type is a reference, just extract the component name.
         ReferenceOr::Reference { reference } => reference.replace("#/components/schemas/", ""),
         ReferenceOr::Item(item) => {
             let mut base = match &item.schema_kind {
                 SchemaKind::Type(t) => match t {
-                    Type::String(_) => "String".to_owned(),
-                    Type::Number(_) => "f64".to_owned(),
-                    Type::Integer(int) => {
-                        let signed = match int.minimum {
-                            Some(x) => x < 0,
-                            None => false,
-                        };
-                        let int_size = match int.maximum {
-                            Some(x) => {
-                                if signed {
-                                    if x <= i8::MAX.into() {
-                                        "i8"
-                                    } else if x <= i16::MAX.into() {
-                                        "i16"
-                                    } else if x <= i32::MAX.into() {
-                                        "i32"
-                                    } else {
-                                        "i64"
-                                    }
-                                } else {
-                                    if x <= u8::MAX.into() {
-                                        "u8"
-                                    } else if x <= u16::MAX.into() {
-                                        "u16"
-                                    } else if x <= u32::MAX.into() {
-                                        "u32"
-                                    } else {
-                                        "u64"
-                                    }
-                                }
-                            }
-                            None => "i64",
-                        };
-                        int_size.to_owned()
+                    // Enumerated strings get their own enum.
+                    Type::String(x) if x.enumeration.iter().any(Option::is_some) => {
+                        let description = item.schema_data.description.clone();
+                        ctx.hoist(hint, item, |ctx, name| {
+                            structgen::generate_enum(
+                                name,
+                                &x.enumeration,
+                                structgen::enum_default(item),
+                                description,
+                                ctx.enum_fallback,
+                            )
+                        })
                     }
-                    // JSON object, but Rust has no easy way to support this, so just ask for a string.
-                    Type::Object(_) => {
-                        "Option<std::collections::HashMap<String, serde_json::Value>>".to_owned()
+                    Type::String(_) => format_of(t)
+                        .and_then(|f| ctx.format_type(&f))
+                        .unwrap_or_else(|| "String".to_owned()),
+                    Type::Number(_) => format_of(t)
+                        .and_then(|f| ctx.format_type(&f))
+                        .unwrap_or_else(|| "f64".to_owned()),
+                    Type::Integer(int) => format_of(t)
+                        .and_then(|f| ctx.format_type(&f))
+                        .unwrap_or_else(|| integer_type(int)),
+                    // Objects with a known structure get a struct of their own.
+                    Type::Object(x) if !x.properties.is_empty() => {
+                        ctx.hoist(hint, item, |ctx, name| {
+                            structgen::generate(name, item, ctx).unwrap_or_default()
+                        })
                     }
+                    // Maps with a known value type.
+                    Type::Object(x) => match &x.additional_properties {
+                        Some(AdditionalProperties::Schema(value)) => {
+                            let value = type_to_string(value, &format!("{}Value", hint), ctx);
+                            ctx.map_type(&value)
+                        }
+                        // JSON object, but Rust has no easy way to support this, so just ask for a string.
+                        _ => format!("Option<{}>", ctx.map_type("serde_json::Value")),
+                    },
                     Type::Boolean(_) => "bool".to_owned(),
-                    Type::Array(x) => {
-                        let items = x.items.as_ref().unwrap().clone().unbox();
-                        format!("Vec<{}>", type_to_string(&items))
-                    }
+                    Type::Array(x) => match &x.items {
+                        Some(items) => {
+                            let items = items.clone().unbox();
+                            format!("Vec<{}>", type_to_string(&items, hint, ctx))
+                        }
+                        // Items of any type.
+                        None => "Vec<serde_json::Value>".to_owned(),
+                    },
                 },
-                SchemaKind::AllOf { all_of } => {
-                    format!("Option<{}>", type_to_string(&all_of[0].clone()))
+                // A single member is usually a reference made nullable, which is handled below.
+                SchemaKind::AllOf { all_of } if all_of.len() == 1 => {
+                    type_to_string(&all_of[0], hint, ctx)
+                }
+                // Compositions are merged into a struct of their own.
+                SchemaKind::AllOf { all_of } if !all_of.is_empty() => {
+                    ctx.hoist(hint, item, |ctx, name| {
+                        structgen::generate(name, item, ctx).unwrap_or_default()
+                    })
+                }
+                // A single member doesn't need an enum around it.
+                SchemaKind::OneOf { one_of: members } | SchemaKind::AnyOf { any_of: members }
+                    if members.len() == 1 =>
+                {
+                    type_to_string(&members[0], hint, ctx)
+                }
+                SchemaKind::OneOf { one_of: members } | SchemaKind::AnyOf { any_of: members }
+                    if !members.is_empty() =>
+                {
+                    ctx.hoist(hint, item, |ctx, name| {
+                        structgen::generate_union(name, item, members, ctx)
+                    })
                 }
                 // Very likely a JSON object.
                 _ => "serde_json::Value".to_owned(),
@@ -194,12 +896,82 @@ pub fn type_to_string(ty: &ReferenceOr<Schema>) -> String {
     }
 }
 
+/// Get the `format` of a string, number or integer schema.
+fn format_of(typ: &Type) -> Option<String> {
+    match typ {
+        Type::String(x) => format_name(&x.format, |f| match f {
+            StringFormat::Date => "date",
+            StringFormat::DateTime => "date-time",
+            StringFormat::Password => "password",
+            StringFormat::Byte => "byte",
+            StringFormat::Binary => "binary",
+        }),
+        Type::Number(x) => format_name(&x.format, |f| match f {
+            NumberFormat::Float => "float",
+            NumberFormat::Double => "double",
+        }),
+        Type::Integer(x) => format_name(&x.format, |f| match f {
+            IntegerFormat::Int32 => "int32",
+            IntegerFormat::Int64 => "int64",
+        }),
+        _ => None,
+    }
+}
+
+/// Get the name of a schema `format`, with `name` naming the formats known to `openapiv3`.
+fn format_name<T>(
+    format: &VariantOrUnknownOrEmpty<T>,
+    name: impl FnOnce(&T) -> &'static str,
+) -> Option<String> {
+    match format {
+        VariantOrUnknownOrEmpty::Item(x) => Some(name(x).to_owned()),
+        VariantOrUnknownOrEmpty::Unknown(x) => Some(x.clone()),
+        VariantOrUnknownOrEmpty::Empty => None,
+    }
+}
+
+/// Get the smallest integer type fitting the bounds of an integer schema.
+fn integer_type(int: &IntegerType) -> String {
+    let signed = match int.minimum {
+        Some(x) => x < 0,
+        None => false,
+    };
+    let int_size = match int.maximum {
+        Some(x) => {
+            if signed {
+                if x <= i8::MAX.into() {
+                    "i8"
+                } else if x <= i16::MAX.into() {
+                    "i16"
+                } else if x <= i32::MAX.into() {
+                    "i32"
+                } else {
+                    "i64"
+                }
+            } else {
+                if x <= u8::MAX.into() {
+                    "u8"
+                } else if x <= u16::MAX.into() {
+                    "u16"
+                } else if x <= u32::MAX.into() {
+                    "u32"
+                } else {
+                    "u64"
+                }
+            }
+        }
+        None => "i64",
+    };
+    int_size.to_owned()
+}
+
 #[cfg(test)]
 mod tests {
     use openapiv3::{IntegerType, StringType, Type};
     use tempfile::TempDir;
 
     use super::*;
+    use crate::config::PackageInfo;
 
     #[test]
     fn test_make_comment() {
@@ -223,7 +995,10 @@ mod tests {
                 ..Default::default()
             })),
         });
-        assert_eq!(type_to_string(&schema), "String");
+        assert_eq!(
+            type_to_string(&schema, "", &mut TypeContext::default()),
+            "String"
+        );
     }
 
     #[test]
@@ -236,7 +1011,44 @@ mod tests {
                 ..Default::default()
             })),
         });
-        assert_eq!(type_to_string(&schema), "i64");
+        assert_eq!(
+            type_to_string(&schema, "", &mut TypeContext::default()),
+            "i64"
+        );
+    }
+
+    #[test]
+    fn test_type_to_string_format() {
+        let date_time: ReferenceOr<Schema> =
+            serde_yaml::from_str("type: string\nformat: date-time\n").unwrap();
+        let ipv4: ReferenceOr<Schema> =
+            serde_yaml::from_str("type: string\nformat: ipv4\n").unwrap();
+        let float: ReferenceOr<Schema> =
+            serde_yaml::from_str("type: number\nformat: float\n").unwrap();
+        let int32: ReferenceOr<Schema> =
+            serde_yaml::from_str("type: integer\nformat: int32\n").unwrap();
+        let byte: ReferenceOr<Schema> =
+            serde_yaml::from_str("type: string\nformat: byte\n").unwrap();
+        let binary: ReferenceOr<Schema> =
+            serde_yaml::from_str("type: string\nformat: binary\n").unwrap();
+        let mut ctx = TypeContext::default();
+        assert_eq!(
+            type_to_string(&date_time, "", &mut ctx),
+            "chrono::DateTime<chrono::Utc>"
+        );
+        assert_eq!(type_to_string(&ipv4, "", &mut ctx), "std::net::IpAddr");
+        assert_eq!(type_to_string(&float, "", &mut ctx), "f32");
+        assert_eq!(type_to_string(&int32, "", &mut ctx), "i64");
+        assert_eq!(type_to_string(&byte, "", &mut ctx), "crate::util::Base64");
+        assert_eq!(type_to_string(&binary, "", &mut ctx), "String");
+
+        // Mappings can be overridden and extended.
+        ctx.type_mappings
+            .insert("date-time".to_owned(), "String".to_owned());
+        ctx.type_mappings
+            .insert("int32".to_owned(), "i32".to_owned());
+        assert_eq!(type_to_string(&date_time, "", &mut ctx), "String");
+        assert_eq!(type_to_string(&int32, "", &mut ctx), "i32");
     }
 
     #[test]
@@ -244,15 +1056,126 @@ mod tests {
         let schema = ReferenceOr::Reference {
             reference: "#/components/schemas/User".to_string(),
         };
-        assert_eq!(type_to_string(&schema), "User");
+        assert_eq!(
+            type_to_string(&schema, "", &mut TypeContext::default()),
+            "User"
+        );
     }
 
     #[test]
-    fn test_create_lib_dir() -> io::Result<()> {
-        let temp_dir = TempDir::new()?;
+    fn test_type_to_string_enum() {
+        let schema = ReferenceOr::Item(Schema {
+            schema_data: Default::default(),
+            schema_kind: SchemaKind::Type(Type::String(StringType {
+                enumeration: vec![Some("active".to_owned()), Some("planned".to_owned()), None],
+                ..Default::default()
+            })),
+        });
+        let mut ctx = TypeContext::default();
+        assert_eq!(
+            type_to_string(&schema, "DeviceStatus", &mut ctx),
+            "DeviceStatus"
+        );
+        // The same schema is only hoisted once.
+        assert_eq!(
+            type_to_string(&schema, "DeviceStatus", &mut ctx),
+            "DeviceStatus"
+        );
+        assert_eq!(ctx.hoisted.len(), 1);
+        assert!(ctx.hoisted[0].contains("pub enum DeviceStatus {"));
+    }
+
+    #[test]
+    fn test_type_to_string_all_of() {
+        let nullable: ReferenceOr<Schema> = serde_yaml::from_str(
+            "allOf:\n  - $ref: '#/components/schemas/Tenant'\nnullable: true\n",
+        )
+        .unwrap();
+        let required: ReferenceOr<Schema> =
+            serde_yaml::from_str("allOf:\n  - $ref: '#/components/schemas/Tenant'\n").unwrap();
+        let mut ctx = TypeContext::default();
+        assert_eq!(type_to_string(&nullable, "", &mut ctx), "Option<Tenant>");
+        assert_eq!(type_to_string(&required, "", &mut ctx), "Tenant");
+    }
+
+    #[test]
+    fn test_type_to_string_inline_object() {
+        let schema: ReferenceOr<Schema> = serde_yaml::from_str(
+            "type: object\nnullable: true\nproperties:\n\
+             \x20 value: {type: string, enum: [active, offline]}\n",
+        )
+        .unwrap();
+        let mut ctx = TypeContext::default();
+        assert_eq!(
+            type_to_string(&schema, "DeviceStatus", &mut ctx),
+            "Option<DeviceStatus>"
+        );
+        // Nested inline types are hoisted before their parent is finished.
+        assert_eq!(ctx.hoisted.len(), 2);
+        assert!(ctx.hoisted[0].contains("pub enum DeviceStatusValue {"));
+        assert!(ctx.hoisted[1].contains("pub struct DeviceStatus {"));
+        assert!(ctx.hoisted[1].contains("pub value: Option<DeviceStatusValue>,"));
+    }
+
+    #[test]
+    fn test_type_to_string_map() {
+        let typed: ReferenceOr<Schema> =
+            serde_yaml::from_str("type: object\nadditionalProperties: {type: integer}\n").unwrap();
+        let referenced: ReferenceOr<Schema> = serde_yaml::from_str(
+            "type: object\nadditionalProperties:\n  $ref: '#/components/schemas/Tag'\n",
+        )
+        .unwrap();
+        let free_form: ReferenceOr<Schema> = serde_yaml::from_str("type: object\n").unwrap();
+        let mut ctx = TypeContext::default();
+        assert_eq!(
+            type_to_string(&typed, "", &mut ctx),
+            "std::collections::HashMap<String, i64>"
+        );
+        assert_eq!(
+            type_to_string(&free_form, "", &mut ctx),
+            "Option<std::collections::HashMap<String, serde_json::Value>>"
+        );
+        ctx.btree_maps = true;
+        assert_eq!(
+            type_to_string(&referenced, "", &mut ctx),
+            "std::collections::BTreeMap<String, Tag>"
+        );
+    }
+
+    #[test]
+    fn test_type_to_string_array() {
+        let typed: ReferenceOr<Schema> =
+            serde_yaml::from_str("type: array\nitems: {type: integer}\n").unwrap();
+        let untyped: ReferenceOr<Schema> = serde_yaml::from_str("type: array\n").unwrap();
+        let mut ctx = TypeContext::default();
+        assert_eq!(type_to_string(&typed, "", &mut ctx), "Vec<i64>");
+        assert_eq!(
+            type_to_string(&untyped, "", &mut ctx),
+            "Vec<serde_json::Value>"
+        );
+    }
+
+    #[test]
+    fn test_hoist_name_collision() {
+        let mut ctx = TypeContext::default();
+        ctx.reserve("DeviceStatus");
+        let schema = Schema {
+            schema_data: Default::default(),
+            schema_kind: SchemaKind::Type(Type::String(StringType::default())),
+        };
+        let name = ctx.hoist("DeviceStatus", &schema, |_, name| name.to_owned());
+        assert_eq!(name, "DeviceStatus2");
+        assert_eq!(ctx.hoisted, vec!["DeviceStatus2".to_owned()]);
+    }
+
+    #[test]
+    fn test_create_lib_dir() -> Result<(), ThanixError> {
+        let temp_dir = TempDir::new().unwrap();
         let output_path = temp_dir.path();
 
-        create_lib_dir(output_path)?;
+        Generator::default()
+            .generate(&OpenAPI::default())?
+            .write(output_path)?;
 
         assert!(output_path.join("src").exists());
         assert!(output_path.join("src/util.rs").exists());
@@ -261,23 +1184,274 @@ mod tests {
         assert!(output_path.join("build.rs").exists());
         assert!(output_path.join("README.md").exists());
 
-        let cargo_content = fs::read_to_string(output_path.join("Cargo.toml"))?;
+        let cargo_content = fs::read_to_string(output_path.join("Cargo.toml")).unwrap();
         assert!(cargo_content.contains("[package]"));
         assert!(cargo_content.contains("edition = \"2024\""));
+        assert!(cargo_content.contains("name = \"thanix_client\""));
+
+        let util_content = fs::read_to_string(output_path.join("src/util.rs")).unwrap();
+        assert!(util_content.contains("pub fn builder(base_url: impl Into<String>)"));
+        // The helpers of the types are available to them, too.
+        assert!(util_content.contains("pub fn deserialize_some<"));
 
         Ok(())
     }
 
     #[test]
-    fn test_create_lib_dir_custom_name() -> io::Result<()> {
-        let temp_dir = TempDir::new()?;
-        let output_path = temp_dir.path().join("custom-name");
-
-        create_lib_dir(&output_path)?;
+    fn test_create_lib_dir_custom_name() -> Result<(), ThanixError> {
+        let config = GeneratorConfig::builder().crate_name("custom-name").build();
+        let output = Generator::new(config).generate(&OpenAPI::default())?;
 
-        let cargo_content = fs::read_to_string(output_path.join("Cargo.toml"))?;
+        let cargo_content = &output.files[Path::new("Cargo.toml")];
         assert!(cargo_content.contains("name = \"custom-name\""));
 
         Ok(())
     }
+
+    #[test]
+    fn test_create_lib_dir_package_escaped() -> Result<(), ThanixError> {
+        let package = PackageInfo {
+            authors: vec!["Jane \"JD\" Doe <jd@example.com>".to_owned()],
+            description: "Client for C:\\NetBox\nwith \u{1f4e6} unicode".to_owned(),
+            ..Default::default()
+        };
+        let config = GeneratorConfig::builder().package(package.clone()).build();
+        for output in [
+            Generator::new(config.clone()).generate(&OpenAPI::default())?,
+            Generator::new(config).generate_types(&Components::default())?,
+        ] {
+            let manifest: toml::Table =
+                toml::from_str(&output.files[Path::new("Cargo.toml")]).unwrap();
+            assert_eq!(
+                manifest["package"]["authors"],
+                toml::Value::from(package.authors.clone())
+            );
+            assert_eq!(
+                manifest["package"]["description"].as_str(),
+                Some(package.description.as_str())
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_create_lib_dir_async() -> Result<(), ThanixError> {
+        let config = GeneratorConfig::builder().async_mode(true).build();
+        let output = Generator::new(config).generate(&OpenAPI::default())?;
+
+        let lib_content = &output.files[Path::new("src/lib.rs")];
+        assert!(lib_content.contains("#[cfg(feature = \"async\")]\npub mod async_paths {\n"));
+        let cargo_content = &output.files[Path::new("Cargo.toml")];
+        assert!(cargo_content.contains("async = []"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_generate_warnings() -> Result<(), ThanixError> {
+        let api: OpenAPI = serde_yaml::from_str(
+            "openapi: 3.0.3\ninfo: {title: Test, version: '1'}\npaths: {}\n\
+             components:\n  schemas:\n    Name: {type: string}\n",
+        )
+        .unwrap();
+        let output = Generator::default().generate(&api)?;
+        assert_eq!(
+            output.warnings,
+            vec!["Structure Name couldn't be generated"]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_generate_resolves_refs() -> Result<(), ThanixError> {
+        let api: OpenAPI = serde_yaml::from_str(
+            "openapi: 3.0.3\ninfo: {title: Test, version: '1'}\n\
+             paths:\n\
+             \x20 /api/devices/:\n\
+             \x20   get:\n\
+             \x20     operationId: devices_list\n\
+             \x20     parameters: [{$ref: '#/components/parameters/limit'}]\n\
+             \x20     responses: {'200': {$ref: '#/components/responses/ok'}}\n\
+             components:\n\
+             \x20 parameters:\n\
+             \x20   limit: {name: limit, in: query, schema: {type: integer}}\n\
+             \x20 responses:\n\
+             \x20   ok: {description: OK, content: {application/json: {schema: {$ref: '#/components/schemas/Alias'}}}}\n\
+             \x20 schemas:\n\
+             \x20   Alias: {$ref: '#/components/schemas/Device'}\n\
+             \x20   Device: {type: object, properties: {id: {type: integer}}}\n",
+        )
+        .unwrap();
+        let output = Generator::default().generate(&api)?;
+
+        let paths = &output.files[Path::new("src/paths/devices.rs")];
+        assert!(paths.contains("pub limit: Option<i64>,"));
+        assert!(paths.contains("Http200(Alias)"));
+        let types = &output.files[Path::new("src/types/devices.rs")];
+        assert!(types.contains("pub type Alias = Device;"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_generate_path_parameters() -> Result<(), ThanixError> {
+        let api: OpenAPI = serde_yaml::from_str(
+            "openapi: 3.0.3\ninfo: {title: Test, version: '1'}\n\
+             paths:\n\
+             \x20 /api/dcim/devices/{id}/:\n\
+             \x20   parameters: [{name: id, in: path, required: true, schema: {type: integer}}]\n\
+             \x20   get: {operationId: dcim_devices_retrieve, tags: [dcim], responses: {}}\n\
+             \x20   delete: {operationId: dcim_devices_destroy, tags: [dcim], responses: {}}\n",
+        )
+        .unwrap();
+        let output = Generator::default().generate(&api)?;
+
+        let paths = &output.files[Path::new("src/paths/dcim.rs")];
+        // The parameter of the path is an argument of all its operations.
+        assert!(paths.contains("pub fn dcim_devices_retrieve(state: &ThanixClient, id: i64)"));
+        assert!(paths.contains("pub fn dcim_devices_destroy(state: &ThanixClient, id: i64)"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_generate_modules() -> Result<(), ThanixError> {
+        let api: OpenAPI = serde_yaml::from_str(
+            "openapi: 3.0.3\ninfo: {title: Test, version: '1'}\n\
+             paths:\n\
+             \x20 /api/dcim/devices/:\n\
+             \x20   get:\n\
+             \x20     operationId: dcim_devices_list\n\
+             \x20     tags: [dcim]\n\
+             \x20     responses: {'200': {description: OK, content: {application/json: {schema: {$ref: '#/components/schemas/Device'}}}}}\n\
+             \x20 /api/status/:\n\
+             \x20   get:\n\
+             \x20     operationId: status_retrieve\n\
+             \x20     responses: {'200': {description: OK, content: {application/json: {schema: {type: object, properties: {ok: {type: boolean}}}}}}}\n\
+             components:\n\
+             \x20 schemas:\n\
+             \x20   Device: {type: object, properties: {name: {type: string}}}\n\
+             \x20   Unused: {type: object, properties: {name: {type: string}}}\n",
+        )
+        .unwrap();
+        let config = GeneratorConfig::builder().async_mode(true).build();
+        let output = Generator::new(config).generate(&api)?;
+
+        let paths: Vec<&Path> = output.files.keys().map(PathBuf::as_path).collect();
+        for module in [
+            "src/auth.rs",
+            "src/paths/dcim.rs",
+            "src/paths/status.rs",
+            "src/async_paths/dcim.rs",
+            "src/types/dcim.rs",
+            "src/types/shared.rs",
+            "src/types/status.rs",
+        ] {
+            assert!(paths.contains(&Path::new(module)), "{} is missing", module);
+        }
+        assert!(output.files[Path::new("src/types/dcim.rs")].contains("pub struct Device {"));
+        assert!(output.files[Path::new("src/types/shared.rs")].contains("pub struct Unused {"));
+        // Types hoisted out of an operation belong to its module.
+        assert!(
+            output.files[Path::new("src/types/status.rs")]
+                .contains("pub struct StatusRetrieveResponse200 {")
+        );
+        // Operations are methods of the client, too.
+        assert!(
+            output.files[Path::new("src/paths/status.rs")]
+                .contains("\tpub fn status(&self) -> StatusApi<'_> {\n")
+        );
+        let lib = &output.files[Path::new("src/lib.rs")];
+        assert!(lib.contains(
+            "pub mod paths {\n\t#[cfg(feature = \"dcim\")]\n\tpub mod dcim;\n\t#[cfg(feature = \"dcim\")]\n\tpub use dcim::*;\n"
+        ));
+        assert!(lib.contains("\tpub mod shared;\n\tpub use shared::*;\n"));
+        let cargo = &output.files[Path::new("Cargo.toml")];
+        assert!(cargo.contains("default = [\"blocking\", \"full\"]\n"));
+        assert!(cargo.contains("full = [\"dcim\", \"status\"]\ndcim = []\nstatus = []\n"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_generate_types() -> Result<(), ThanixError> {
+        let components: Components = serde_yaml::from_str(
+            "schemas:\n  Blob: {type: object, properties: {data: {type: string, format: byte}}}\n",
+        )
+        .unwrap();
+
+        let output = Generator::default().generate_types(&components)?;
+        let paths: Vec<&Path> = output.files.keys().map(PathBuf::as_path).collect();
+        assert_eq!(
+            paths,
+            [
+                "Cargo.toml",
+                "README.md",
+                "src/lib.rs",
+                "src/types.rs",
+                "src/util.rs"
+            ]
+            .map(Path::new)
+        );
+        assert!(!output.files[Path::new("Cargo.toml")].contains("reqwest"));
+        assert!(output.files[Path::new("src/types.rs")].contains("Option<crate::util::Base64>"));
+
+        let output = Generator::default().generate_types_module(&components)?;
+        let module = &output.files[Path::new("types.rs")];
+        assert!(module.contains("Option<self::util::Base64>"));
+        assert!(module.contains("pub mod util {\n"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_removes_stale_files() -> Result<(), ThanixError> {
+        let dir = tempfile::TempDir::new().unwrap();
+        // A module of a previous run, next to the `paths` directory of this one.
+        fs::create_dir_all(dir.path().join("src/paths")).unwrap();
+        fs::write(dir.path().join("src/paths.rs"), "").unwrap();
+        fs::write(dir.path().join("src/paths/dropped.rs"), "").unwrap();
+        fs::write(dir.path().join("thanix.toml"), "").unwrap();
+
+        Generator::default()
+            .generate(&OpenAPI::default())?
+            .write(dir.path())?;
+        assert!(dir.path().join("src/lib.rs").exists());
+        assert!(!dir.path().join("src/paths.rs").exists());
+        assert!(!dir.path().join("src/paths/dropped.rs").exists());
+        // Files outside of `src` are kept.
+        assert!(dir.path().join("thanix.toml").exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_tar() -> Result<(), ThanixError> {
+        let output = Generator::default().generate(&OpenAPI::default())?;
+        let mut tarball = Vec::new();
+        output.write_tar(&mut tarball).unwrap();
+
+        let mut archive = tar::Archive::new(tarball.as_slice());
+        let paths: Vec<PathBuf> = archive
+            .entries()
+            .unwrap()
+            .map(|e| e.unwrap().path().unwrap().into_owned())
+            .collect();
+        assert!(paths.contains(&PathBuf::from("thanix_client/Cargo.toml")));
+        assert!(paths.contains(&PathBuf::from("thanix_client/src/lib.rs")));
+        assert_eq!(paths.len(), output.files.len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_to_bundle() -> Result<(), ThanixError> {
+        let output = Generator::default().generate(&OpenAPI::default())?;
+        let bundle = output.to_bundle();
+        assert!(bundle.starts_with("==> Cargo.toml <==\n[package]\n"));
+        assert!(bundle.contains("\n==> src/lib.rs <==\n"));
+
+        Ok(())
+    }
 }
@@ -0,0 +1,405 @@
+//! Bundle specs which are split across several files into a single document.
+//!
+//! References into other files, like `common.yaml#/components/schemas/Tag`, are rewritten so the
+//! document only contains local references. Schemas are imported into `components.schemas` of
+//! the bundled document, so they still get a name of their own. Everything else, e.g. shared
+//! parameters, is copied to where it is referenced.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use convert_case::{Case, Casing};
+use serde_yaml::{Mapping, Value};
+
+use crate::error::ThanixError;
+use crate::resolve;
+
+/// Bundle the document `doc` read from `path` with all files it references.
+///
+/// The file at `path` doesn't need to exist, e.g. for a document read from stdin, but its directory
+/// does, as references are relative to it.
+pub fn bundle(mut doc: Value, path: &Path) -> Result<Value, ThanixError> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let root = canonicalize(dir)?.join(path.file_name().unwrap_or_default());
+    let taken = doc
+        .get("components")
+        .and_then(|c| c.get("schemas"))
+        .and_then(Value::as_mapping)
+        .map(|schemas| {
+            schemas
+                .keys()
+                .filter_map(|k| k.as_str().map(str::to_owned))
+                .collect()
+        })
+        .unwrap_or_default();
+    let mut bundler = Bundler {
+        root: root.clone(),
+        files: HashMap::new(),
+        imported: HashMap::new(),
+        schemas: Vec::new(),
+        taken,
+        stack: Vec::new(),
+    };
+    bundler.bundle_value(&mut doc, &root, &mut Vec::new())?;
+
+    if !bundler.schemas.is_empty() {
+        let schemas = doc
+            .as_mapping_mut()
+            .and_then(|doc| mapping_entry(doc, "components"))
+            .and_then(|components| mapping_entry(components, "schemas"))
+            .ok_or_else(|| ThanixError::schema(&[], "document is not a mapping"))?;
+        for (name, schema) in bundler.schemas {
+            schemas.insert(Value::String(name), schema);
+        }
+    }
+    Ok(doc)
+}
+
+/// Read and parse the document at `path`, or from stdin if it is `-`.
+pub fn read_document(path: &Path) -> Result<Value, ThanixError> {
+    let io_error = |source| ThanixError::Io {
+        path: path.to_owned(),
+        source,
+    };
+    let contents = if path == Path::new("-") {
+        io::read_to_string(io::stdin()).map_err(io_error)?
+    } else {
+        fs::read_to_string(path).map_err(io_error)?
+    };
+    parse_document(path, &contents)
+}
+
+/// Parse a document read from `path`, as JSON if its extension or contents say so, or as YAML.
+pub fn parse_document(path: &Path, contents: &str) -> Result<Value, ThanixError> {
+    let is_json = match path.extension().and_then(|e| e.to_str()) {
+        Some(ext) if ext.eq_ignore_ascii_case("json") => true,
+        Some(ext) if ext.eq_ignore_ascii_case("yaml") || ext.eq_ignore_ascii_case("yml") => false,
+        _ => contents.trim_start().starts_with(['{', '[']),
+    };
+    if is_json {
+        let doc: serde_json::Value =
+            serde_json::from_str(contents).map_err(|source| ThanixError::ParseJson {
+                path: path.to_owned(),
+                source,
+            })?;
+        serde_yaml::to_value(doc).map_err(|source| ThanixError::Parse {
+            path: path.to_owned(),
+            source,
+        })
+    } else {
+        serde_yaml::from_str(contents).map_err(|source| ThanixError::Parse {
+            path: path.to_owned(),
+            source,
+        })
+    }
+}
+
+/// Get the mapping at `key` of `map`, inserting an empty one if there is none.
+fn mapping_entry<'a>(map: &'a mut Mapping, key: &str) -> Option<&'a mut Mapping> {
+    map.entry(Value::String(key.to_owned()))
+        .or_insert_with(|| Value::Mapping(Mapping::new()))
+        .as_mapping_mut()
+}
+
+fn canonicalize(path: &Path) -> Result<PathBuf, ThanixError> {
+    fs::canonicalize(path).map_err(|source| ThanixError::Io {
+        path: path.to_owned(),
+        source,
+    })
+}
+
+/// Check whether the value at `path` within a document is a schema.
+pub fn is_schema_position(path: &[String]) -> bool {
+    let last = path.last().map(String::as_str);
+    let parent = path.len().checked_sub(2).map(|i| path[i].as_str());
+    matches!(
+        last,
+        Some("schema" | "items" | "additionalProperties" | "not")
+    ) || matches!(
+        parent,
+        Some("properties" | "allOf" | "oneOf" | "anyOf" | "schemas" | "definitions" | "$defs")
+    )
+}
+
+struct Bundler {
+    /// The file the bundled document was read from.
+    root: PathBuf,
+    /// Referenced documents, keyed by their canonical path.
+    files: HashMap<PathBuf, Value>,
+    /// Names of the imported schemas, keyed by file and pointer.
+    imported: HashMap<(PathBuf, String), String>,
+    /// The imported schemas, in the order they were found.
+    schemas: Vec<(String, Value)>,
+    /// All schema names which are already used.
+    taken: HashSet<String>,
+    /// The references currently being copied, to detect cycles.
+    stack: Vec<(PathBuf, String)>,
+}
+
+impl Bundler {
+    /// Rewrite all references within `value`, which is found in `file` at `path`.
+    fn bundle_value(
+        &mut self,
+        value: &mut Value,
+        file: &Path,
+        path: &mut Vec<String>,
+    ) -> Result<(), ThanixError> {
+        match value {
+            Value::Mapping(map) => {
+                if let Some(Value::String(reference)) = map.get("$ref") {
+                    let reference = reference.clone();
+                    return self.bundle_ref(value, &reference, file, path);
+                }
+                for (key, v) in map.iter_mut() {
+                    path.push(key.as_str().unwrap_or_default().to_owned());
+                    let result = self.bundle_value(v, file, path);
+                    path.pop();
+                    result?;
+                }
+            }
+            Value::Sequence(items) => {
+                for (i, v) in items.iter_mut().enumerate() {
+                    path.push(i.to_string());
+                    let result = self.bundle_value(v, file, path);
+                    path.pop();
+                    result?;
+                }
+            }
+            Value::Tagged(tagged) => self.bundle_value(&mut tagged.value, file, path)?,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Replace the reference `value` to `reference` by a local one or a copy of its target.
+    fn bundle_ref(
+        &mut self,
+        value: &mut Value,
+        reference: &str,
+        file: &Path,
+        path: &mut Vec<String>,
+    ) -> Result<(), ThanixError> {
+        let (target, fragment) = match reference.split_once('#') {
+            Some(("", fragment)) => (file.to_owned(), fragment.to_owned()),
+            Some((target, fragment)) => (self.locate(file, target)?, fragment.to_owned()),
+            None => (self.locate(file, reference)?, String::new()),
+        };
+        if target == self.root {
+            // Local references of the bundled document stay as they are.
+            *value = local_ref(&fragment);
+            return Ok(());
+        }
+
+        let name = fragment.strip_prefix("/components/schemas/");
+        if name.is_some_and(|name| !name.contains('/')) || is_schema_position(path) {
+            let name = self.import(&target, &fragment)?;
+            *value = local_ref(&format!("/components/schemas/{}", name));
+            return Ok(());
+        }
+
+        // Everything else is copied, with its own references rewritten.
+        let key = (target.clone(), fragment.clone());
+        if self.stack.contains(&key) {
+            return Err(ThanixError::Schema {
+                pointer: format!("{}#{}", target.display(), fragment),
+                message: "`$ref` cycle".to_owned(),
+            });
+        }
+        *value = self.lookup(&target, &fragment)?;
+        self.stack.push(key);
+        let result = self.bundle_value(value, &target, path);
+        self.stack.pop();
+        result
+    }
+
+    /// Import the schema at `fragment` of `file` into the bundled document and get its name.
+    fn import(&mut self, file: &Path, fragment: &str) -> Result<String, ThanixError> {
+        let key = (file.to_owned(), fragment.to_owned());
+        if let Some(name) = self.imported.get(&key) {
+            return Ok(name.clone());
+        }
+
+        let stem = file
+            .file_stem()
+            .map(|x| x.to_string_lossy().to_case(Case::Pascal))
+            .unwrap_or_default();
+        // Name the schema like in its own file, or after the file if it's the whole file.
+        let base = match fragment.rsplit('/').next() {
+            Some(segment) if !segment.is_empty() => segment.replace("~1", "/").replace("~0", "~"),
+            _ => stem.clone(),
+        };
+        let mut name = base.clone();
+        if self.taken.contains(&name) {
+            name = format!("{}{}", stem, base);
+        }
+        let mut i = 2;
+        while self.taken.contains(&name) {
+            name = format!("{}{}{}", stem, base, i);
+            i += 1;
+        }
+        self.taken.insert(name.clone());
+        // Registered before bundling the schema itself, so it can reference itself.
+        self.imported.insert(key, name.clone());
+
+        let mut schema = self.lookup(file, fragment)?;
+        let mut path = vec!["components".to_owned(), "schemas".to_owned(), name.clone()];
+        self.bundle_value(&mut schema, file, &mut path)?;
+        self.schemas.push((name.clone(), schema));
+        Ok(name)
+    }
+
+    /// Find the file `target` referenced from `file`.
+    fn locate(&self, file: &Path, target: &str) -> Result<PathBuf, ThanixError> {
+        canonicalize(&file.parent().unwrap_or(Path::new(".")).join(target))
+    }
+
+    /// Get a copy of the value at `fragment` of `file`.
+    fn lookup(&mut self, file: &Path, fragment: &str) -> Result<Value, ThanixError> {
+        if !self.files.contains_key(file) {
+            let contents = fs::read_to_string(file).map_err(|source| ThanixError::Io {
+                path: file.to_owned(),
+                source,
+            })?;
+            let doc = parse_document(file, &contents)?;
+            self.files.insert(file.to_owned(), doc);
+        }
+        resolve::pointer(&self.files[file], &format!("#{}", fragment))
+            .cloned()
+            .ok_or_else(|| ThanixError::Schema {
+                pointer: format!("{}#{}", file.display(), fragment),
+                message: "`$ref` target doesn't exist".to_owned(),
+            })
+    }
+}
+
+/// A `$ref` to `fragment` of the bundled document.
+fn local_ref(fragment: &str) -> Value {
+    let mut map = Mapping::new();
+    map.insert("$ref".into(), format!("#{}", fragment).into());
+    Value::Mapping(map)
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    fn bundle_files(files: &[(&str, &str)]) -> Result<Value, ThanixError> {
+        let dir = TempDir::new().unwrap();
+        for (name, contents) in files {
+            let path = dir.path().join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        }
+        let root = dir.path().join(files[0].0);
+        bundle(serde_yaml::from_str(files[0].1).unwrap(), &root)
+    }
+
+    #[test]
+    fn test_bundle_schemas() {
+        let doc = bundle_files(&[
+            (
+                "api.yaml",
+                "paths: {}\n\
+                 components:\n\
+                 \x20 schemas:\n\
+                 \x20   Tag: {type: string}\n\
+                 \x20   Device:\n\
+                 \x20     type: object\n\
+                 \x20     properties:\n\
+                 \x20       tags: {type: array, items: {$ref: 'common.yaml#/components/schemas/Tag'}}\n\
+                 \x20       site: {$ref: 'schemas/site.json'}\n\
+                 \x20       local: {$ref: '#/components/schemas/Tag'}\n",
+            ),
+            (
+                "common.yaml",
+                "components:\n\
+                 \x20 schemas:\n\
+                 \x20   Tag:\n\
+                 \x20     type: object\n\
+                 \x20     properties:\n\
+                 \x20       parent: {$ref: '#/components/schemas/Tag'}\n",
+            ),
+            ("schemas/site.json", r#"{"type": "object"}"#),
+        ])
+        .unwrap();
+
+        let schemas = &doc["components"]["schemas"];
+        let device = &schemas["Device"]["properties"];
+        // `Tag` is already taken by the root document.
+        assert_eq!(
+            device["tags"]["items"]["$ref"],
+            "#/components/schemas/CommonTag"
+        );
+        assert_eq!(device["site"]["$ref"], "#/components/schemas/Site");
+        assert_eq!(device["local"]["$ref"], "#/components/schemas/Tag");
+        assert_eq!(
+            schemas["CommonTag"]["properties"]["parent"]["$ref"],
+            "#/components/schemas/CommonTag"
+        );
+        assert_eq!(schemas["Site"]["type"], "object");
+    }
+
+    #[test]
+    fn test_bundle_parameters() {
+        let doc = bundle_files(&[
+            (
+                "api.yaml",
+                "paths:\n\
+                 \x20 /a/:\n\
+                 \x20   get:\n\
+                 \x20     parameters: [{$ref: 'params.yaml#/limit'}]\n",
+            ),
+            (
+                "params.yaml",
+                "limit: {name: limit, in: query, schema: {$ref: '#/Limit'}}\n\
+                 Limit: {type: integer}\n",
+            ),
+        ])
+        .unwrap();
+
+        let param = &doc["paths"]["/a/"]["get"]["parameters"][0];
+        assert_eq!(param["name"], "limit");
+        assert_eq!(param["schema"]["$ref"], "#/components/schemas/Limit");
+        assert_eq!(doc["components"]["schemas"]["Limit"]["type"], "integer");
+    }
+
+    #[test]
+    fn test_parse_document() {
+        let json = r#"{"openapi": "3.0.3", "paths": {}}"#;
+        let doc = parse_document(Path::new("api.json"), json).unwrap();
+        assert_eq!(doc["openapi"], "3.0.3");
+        // Detected by content, e.g. when reading from stdin.
+        let doc = parse_document(Path::new("-"), &format!("\t{}", json)).unwrap();
+        assert_eq!(doc["openapi"], "3.0.3");
+        let doc = parse_document(Path::new("-"), "openapi: 3.0.3\n").unwrap();
+        assert_eq!(doc["openapi"], "3.0.3");
+
+        let err = parse_document(Path::new("api.json"), "openapi: 3.0.3\n").unwrap_err();
+        assert!(matches!(err, ThanixError::ParseJson { .. }));
+    }
+
+    #[test]
+    fn test_bundle_missing_file() {
+        let err = bundle_files(&[("api.yaml", "paths: {/a/: {$ref: 'missing.yaml'}}\n")]);
+        assert!(matches!(err, Err(ThanixError::Io { .. })));
+    }
+
+    #[test]
+    fn test_bundle_cycle() {
+        let err = bundle_files(&[
+            ("api.yaml", "paths: {/a/: {$ref: 'a.yaml'}}\n"),
+            ("a.yaml", "get: {$ref: 'b.yaml'}\n"),
+            ("b.yaml", "responses: {$ref: 'a.yaml'}\n"),
+        ])
+        .unwrap_err();
+        assert!(err.to_string().contains("`$ref` cycle"));
+    }
+}
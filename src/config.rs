@@ -0,0 +1,408 @@
+//! Options controlling what gets generated.
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+
+use crate::error::ThanixError;
+
+/// Configuration of a [`crate::Generator`].
+///
+/// Use [`GeneratorConfig::builder`] to create one, or load it from a [`ConfigFile`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GeneratorConfig {
+    /// Metadata written into the `[package]` section of the generated crate.
+    pub package: PackageInfo,
+    /// Make API object fields optional, even though the schema might state otherwise.
+    pub workaround_mode: bool,
+    /// `Schema` or `Schema.field` glob patterns of what gets loosened in workaround mode.
+    /// If empty, a built-in list of NetBox objects known to be affected is used.
+    pub unsanitary: Vec<String>,
+    /// Additionally generate `async` API functions over `reqwest::Client`.
+    pub async_mode: bool,
+    /// Features enabled by default in the generated crate, besides `full`. `None` keeps
+    /// `["blocking"]`.
+    pub default_features: Option<Vec<String>>,
+    /// Add an `Unknown` variant to all generated enums.
+    pub enum_fallback: bool,
+    /// Custom Rust types for schema formats, overriding the built-in mappings.
+    pub type_mappings: HashMap<String, String>,
+    /// Generate maps as `BTreeMap` instead of `HashMap`.
+    pub btree_maps: bool,
+    /// Which paths of the schema get an API function.
+    pub filter: PathFilter,
+}
+
+/// The `[package]` section of the generated crate.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PackageInfo {
+    /// Name of the generated crate. Defaults to [`crate::DEFAULT_CRATE_NAME`].
+    pub name: Option<String>,
+    pub version: String,
+    pub authors: Vec<String>,
+    pub description: String,
+    pub license: String,
+}
+
+impl Default for PackageInfo {
+    fn default() -> Self {
+        Self {
+            name: None,
+            version: "0.1.0".to_owned(),
+            authors: vec!["Your Name".to_owned()],
+            description: "NetBox API reference client crate generated from the schema at https://demo.netbox.dev/api/schema".to_owned(),
+            license: "MIT".to_owned(),
+        }
+    }
+}
+
+/// Selects the operations which get an API function, by path, tag or `operationId`.
+///
+/// An operation is generated if it matches every non-empty `include` list and none of the
+/// `exclude` lists. If any list is set, only the component schemas used by the generated
+/// operations are kept.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct PathFilter {
+    /// Only generate paths matching one of these, either a prefix like `/api/dcim/` or a glob
+    /// like `/api/dcim/**`. Empty means all paths.
+    pub include: Vec<String>,
+    /// Never generate paths matching one of these, even if included.
+    pub exclude: Vec<String>,
+    /// Only generate operations with one of these tags.
+    pub include_tags: Vec<String>,
+    /// Never generate operations with one of these tags.
+    pub exclude_tags: Vec<String>,
+    /// Only generate operations whose `operationId` matches one of these regular expressions.
+    pub include_operations: Vec<String>,
+    /// Never generate operations whose `operationId` matches one of these regular expressions.
+    pub exclude_operations: Vec<String>,
+}
+
+impl PathFilter {
+    /// Check whether no list is set, so everything gets generated.
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+}
+
+impl GeneratorConfig {
+    /// Start building a configuration with all options at their defaults.
+    pub fn builder() -> GeneratorConfigBuilder {
+        GeneratorConfigBuilder::default()
+    }
+}
+
+/// Builder for a [`GeneratorConfig`].
+#[derive(Debug, Clone, Default)]
+pub struct GeneratorConfigBuilder {
+    config: GeneratorConfig,
+}
+
+impl GeneratorConfigBuilder {
+    pub fn crate_name(mut self, name: impl Into<String>) -> Self {
+        self.config.package.name = Some(name.into());
+        self
+    }
+
+    pub fn package(mut self, package: PackageInfo) -> Self {
+        self.config.package = package;
+        self
+    }
+
+    pub fn workaround_mode(mut self, enabled: bool) -> Self {
+        self.config.workaround_mode = enabled;
+        self
+    }
+
+    /// Loosen the schemas or fields matching `pattern` in workaround mode, e.g. `Device.primary_ip*`.
+    pub fn unsanitary(mut self, pattern: impl Into<String>) -> Self {
+        self.config.unsanitary.push(pattern.into());
+        self
+    }
+
+    pub fn async_mode(mut self, enabled: bool) -> Self {
+        self.config.async_mode = enabled;
+        self
+    }
+
+    pub fn default_features(mut self, features: Vec<String>) -> Self {
+        self.config.default_features = Some(features);
+        self
+    }
+
+    pub fn enum_fallback(mut self, enabled: bool) -> Self {
+        self.config.enum_fallback = enabled;
+        self
+    }
+
+    /// Use `typ` for all schemas with the given `format`.
+    pub fn type_mapping(mut self, format: impl Into<String>, typ: impl Into<String>) -> Self {
+        self.config.type_mappings.insert(format.into(), typ.into());
+        self
+    }
+
+    pub fn btree_maps(mut self, enabled: bool) -> Self {
+        self.config.btree_maps = enabled;
+        self
+    }
+
+    pub fn filter(mut self, filter: PathFilter) -> Self {
+        self.config.filter = filter;
+        self
+    }
+
+    pub fn build(self) -> GeneratorConfig {
+        self.config
+    }
+}
+
+/// Contents of a `thanix.toml` project configuration file.
+///
+/// ```toml
+/// input = "netbox.yaml"
+/// output = "netbox-client"
+///
+/// [package]
+/// name = "netbox-client"
+/// version = "4.1.0"
+///
+/// [generator]
+/// enum-fallback = true
+///
+/// [type-mappings]
+/// date-time = "String"
+///
+/// [workaround]
+/// enabled = true
+/// unsanitary = ["Interface", "Device.primary_ip*"]
+///
+/// [filter]
+/// include = ["/api/dcim/**"]
+/// include-tags = ["dcim", "ipam"]
+/// exclude-operations = ["_bulk_"]
+///
+/// [client]
+/// async = true
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct ConfigFile {
+    /// Schema to generate from. [`from_file`](Self::from_file) resolves it relative to the file.
+    pub input: Option<String>,
+    /// Directory to write the crate into. [`from_file`](Self::from_file) resolves it relative to
+    /// the file.
+    pub output: Option<PathBuf>,
+    pub package: PackageInfo,
+    pub generator: GeneratorSection,
+    pub type_mappings: HashMap<String, String>,
+    pub workaround: WorkaroundSection,
+    pub filter: PathFilter,
+    pub client: ClientSection,
+}
+
+/// The `[generator]` section of a [`ConfigFile`].
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct GeneratorSection {
+    pub enum_fallback: bool,
+    pub btree_maps: bool,
+}
+
+/// The `[workaround]` section of a [`ConfigFile`].
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WorkaroundSection {
+    pub enabled: bool,
+    /// `Schema` or `Schema.field` glob patterns of what to loosen.
+    pub unsanitary: Vec<String>,
+}
+
+/// The `[client]` section of a [`ConfigFile`].
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct ClientSection {
+    #[serde(rename = "async")]
+    pub async_mode: bool,
+    pub default_features: Option<Vec<String>>,
+}
+
+impl ConfigFile {
+    /// Read and parse the configuration file at `path`.
+    ///
+    /// A relative `input` and `output` are resolved against the directory of the file, so they
+    /// don't depend on where Thanix is run from. `-` still reads the schema from stdin.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, ThanixError> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path).map_err(|source| ThanixError::Io {
+            path: path.to_owned(),
+            source,
+        })?;
+        let mut file: Self = toml::from_str(&contents).map_err(|source| ThanixError::Config {
+            path: path.to_owned(),
+            source,
+        })?;
+        if let Some(dir) = path.parent() {
+            if let Some(input) = &file.input
+                && input != "-"
+            {
+                file.input = Some(dir.join(input).to_string_lossy().into_owned());
+            }
+            if let Some(output) = &file.output {
+                file.output = Some(dir.join(output));
+            }
+        }
+        Ok(file)
+    }
+
+    /// Turn the generation options of this file into a builder, so they can be overridden further.
+    pub fn builder(&self) -> GeneratorConfigBuilder {
+        let mut builder = GeneratorConfig::builder()
+            .package(self.package.clone())
+            .workaround_mode(self.workaround.enabled)
+            .async_mode(self.client.async_mode)
+            .enum_fallback(self.generator.enum_fallback)
+            .btree_maps(self.generator.btree_maps)
+            .filter(self.filter.clone());
+        if let Some(features) = &self.client.default_features {
+            builder = builder.default_features(features.clone());
+        }
+        for pattern in &self.workaround.unsanitary {
+            builder = builder.unsanitary(pattern.clone());
+        }
+        for (format, typ) in &self.type_mappings {
+            builder = builder.type_mapping(format.clone(), typ.clone());
+        }
+        builder
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builder_defaults() {
+        assert_eq!(
+            GeneratorConfig::builder().build(),
+            GeneratorConfig::default()
+        );
+    }
+
+    #[test]
+    fn test_builder() {
+        let config = GeneratorConfig::builder()
+            .crate_name("netbox")
+            .workaround_mode(true)
+            .async_mode(true)
+            .type_mapping("date-time", "String")
+            .build();
+
+        assert_eq!(config.package.name.as_deref(), Some("netbox"));
+        assert_eq!(config.package.version, "0.1.0");
+        assert!(config.workaround_mode);
+        assert!(config.async_mode);
+        assert!(!config.enum_fallback);
+        assert!(!config.btree_maps);
+        assert_eq!(config.type_mappings["date-time"], "String");
+    }
+
+    #[test]
+    fn test_path_filter_empty() {
+        let filter = PathFilter {
+            include: vec!["/api/dcim/**".to_owned()],
+            ..Default::default()
+        };
+        assert!(!filter.is_empty());
+        assert!(PathFilter::default().is_empty());
+    }
+
+    #[test]
+    fn test_config_file() {
+        let file: ConfigFile = toml::from_str(
+            r#"
+            input = "netbox.yaml"
+
+            [package]
+            name = "netbox-client"
+            version = "4.1.0"
+            authors = ["Nazara Team"]
+
+            [generator]
+            enum-fallback = true
+
+            [type-mappings]
+            date-time = "String"
+
+            [workaround]
+            enabled = true
+            unsanitary = ["Interface", "Device.primary_ip*"]
+
+            [filter]
+            include-tags = ["dcim"]
+            exclude-operations = ["_bulk_"]
+
+            [client]
+            async = true
+            default-features = ["async"]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(file.input.as_deref(), Some("netbox.yaml"));
+        assert_eq!(file.output, None);
+
+        let config = file.builder().build();
+        assert_eq!(config.package.name.as_deref(), Some("netbox-client"));
+        assert_eq!(config.package.version, "4.1.0");
+        assert_eq!(config.package.authors, ["Nazara Team"]);
+        // Unset keys keep their defaults.
+        assert_eq!(config.package.license, "MIT");
+        assert!(config.enum_fallback);
+        assert!(!config.btree_maps);
+        assert_eq!(config.type_mappings["date-time"], "String");
+        assert!(config.workaround_mode);
+        assert_eq!(config.unsanitary, ["Interface", "Device.primary_ip*"]);
+        assert!(config.async_mode);
+        assert_eq!(config.default_features, Some(vec!["async".to_owned()]));
+        assert_eq!(config.filter.include_tags, ["dcim"]);
+        assert_eq!(config.filter.exclude_operations, ["_bulk_"]);
+    }
+
+    #[test]
+    fn test_config_file_paths() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("thanix.toml");
+        fs::write(
+            &path,
+            "input = \"schemas/netbox.yaml\"\noutput = \"netbox-client\"\n",
+        )
+        .unwrap();
+        let file = ConfigFile::from_file(&path).unwrap();
+        assert_eq!(
+            file.input.map(PathBuf::from),
+            Some(dir.path().join("schemas/netbox.yaml"))
+        );
+        assert_eq!(file.output, Some(dir.path().join("netbox-client")));
+
+        // Absolute paths are kept.
+        let output = dir.path().join("out");
+        fs::write(&path, format!("output = {:?}\n", output)).unwrap();
+        let file = ConfigFile::from_file(&path).unwrap();
+        assert_eq!(file.output, Some(output));
+
+        // Stdin stays stdin.
+        fs::write(&path, "input = \"-\"\n").unwrap();
+        let file = ConfigFile::from_file(&path).unwrap();
+        assert_eq!(file.input.as_deref(), Some("-"));
+    }
+
+    #[test]
+    fn test_config_file_unknown_key() {
+        assert!(toml::from_str::<ConfigFile>("[generator]\nasync = true\n").is_err());
+    }
+}
@@ -0,0 +1,124 @@
+//! Errors which can occur while generating bindings.
+
+use std::{fmt, io, path::PathBuf};
+
+/// Everything that can go wrong while generating bindings.
+#[derive(Debug)]
+pub enum ThanixError {
+    /// A file or directory couldn't be read or written.
+    Io { path: PathBuf, source: io::Error },
+    /// The input isn't a valid OpenAPI document.
+    Parse {
+        path: PathBuf,
+        source: serde_yaml::Error,
+    },
+    /// The input isn't a valid JSON OpenAPI document.
+    ParseJson {
+        path: PathBuf,
+        source: serde_json::Error,
+    },
+    /// The `thanix.toml` configuration file is invalid.
+    Config {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+    /// A pattern in the configuration isn't a valid glob.
+    Pattern {
+        pattern: String,
+        source: glob::PatternError,
+    },
+    /// An `operationId` filter in the configuration isn't a valid regular expression.
+    Regex {
+        pattern: String,
+        source: regex::Error,
+    },
+    /// The schema contains something we can't generate code for.
+    Schema {
+        /// JSON pointer to the offending part of the schema, e.g.
+        /// `#/paths/~1api~1dcim~1devices~1/get/parameters/3`.
+        pointer: String,
+        message: String,
+    },
+}
+
+impl ThanixError {
+    /// Create a [`ThanixError::Schema`] for the part of the schema at `segments`.
+    pub fn schema(segments: &[&str], message: impl Into<String>) -> Self {
+        Self::Schema {
+            pointer: json_pointer(segments),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for ThanixError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            Self::Parse { path, source } => {
+                write!(f, "Failed to parse {}: {}", path.display(), source)
+            }
+            Self::ParseJson { path, source } => {
+                write!(f, "Failed to parse {}: {}", path.display(), source)
+            }
+            Self::Config { path, source } => {
+                write!(f, "Invalid configuration in {}: {}", path.display(), source)
+            }
+            Self::Pattern { pattern, source } => {
+                write!(f, "Invalid pattern `{}`: {}", pattern, source)
+            }
+            Self::Regex { pattern, source } => {
+                write!(f, "Invalid regular expression `{}`: {}", pattern, source)
+            }
+            Self::Schema { pointer, message } => write!(f, "{}: {}", pointer, message),
+        }
+    }
+}
+
+impl std::error::Error for ThanixError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io { source, .. } => Some(source),
+            Self::Parse { source, .. } => Some(source),
+            Self::ParseJson { source, .. } => Some(source),
+            Self::Config { source, .. } => Some(source),
+            Self::Pattern { source, .. } => Some(source),
+            Self::Regex { source, .. } => Some(source),
+            Self::Schema { .. } => None,
+        }
+    }
+}
+
+/// Build a JSON pointer into the schema from its unescaped segments.
+///
+/// `~` and `/` within a segment are escaped as `~0` and `~1`, so `["paths", "/api/"]` becomes
+/// `#/paths/~1api~1`.
+pub fn json_pointer(segments: &[&str]) -> String {
+    let mut result = "#".to_owned();
+    for segment in segments {
+        result += "/";
+        result += &segment.replace('~', "~0").replace('/', "~1");
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_pointer() {
+        assert_eq!(
+            json_pointer(&["paths", "/api/dcim/devices/", "get", "parameters", "3"]),
+            "#/paths/~1api~1dcim~1devices~1/get/parameters/3"
+        );
+        assert_eq!(json_pointer(&["a~b"]), "#/a~0b");
+        assert_eq!(json_pointer(&[]), "#");
+    }
+
+    #[test]
+    fn test_display_schema_error() {
+        let error = ThanixError::schema(&["paths", "/test"], "something is wrong");
+        assert_eq!(error.to_string(), "#/paths/~1test: something is wrong");
+    }
+}
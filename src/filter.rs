@@ -0,0 +1,367 @@
+//! Select the operations to generate and the component schemas they need.
+
+use std::collections::BTreeSet;
+
+use glob::{MatchOptions, Pattern};
+use openapiv3::{Components, Operation, PathItem};
+use regex::Regex;
+use serde_yaml::Value;
+
+use crate::config::PathFilter;
+use crate::error::ThanixError;
+use crate::pathgen;
+
+/// A path pattern of a [`PathFilter`], either a prefix or a glob.
+enum PathPattern {
+    Prefix(String),
+    Glob(Pattern),
+}
+
+impl PathPattern {
+    /// Parse `pattern` as a glob if it contains any wildcards, or as a prefix otherwise.
+    fn new(pattern: &str) -> Result<Self, ThanixError> {
+        if !pattern.contains(['*', '?', '[']) {
+            return Ok(Self::Prefix(pattern.to_owned()));
+        }
+        Pattern::new(pattern)
+            .map(Self::Glob)
+            .map_err(|source| ThanixError::Pattern {
+                pattern: pattern.to_owned(),
+                source,
+            })
+    }
+
+    fn matches(&self, path: &str) -> bool {
+        match self {
+            Self::Prefix(prefix) => path.starts_with(prefix.as_str()),
+            // `*` stays within a segment, only `**` matches across them.
+            Self::Glob(pattern) => pattern.matches_with(
+                path,
+                MatchOptions {
+                    require_literal_separator: true,
+                    ..Default::default()
+                },
+            ),
+        }
+    }
+}
+
+/// A [`PathFilter`] with its patterns compiled.
+pub struct OperationFilter {
+    include: Vec<PathPattern>,
+    exclude: Vec<PathPattern>,
+    include_tags: Vec<String>,
+    exclude_tags: Vec<String>,
+    include_operations: Vec<Regex>,
+    exclude_operations: Vec<Regex>,
+}
+
+impl OperationFilter {
+    /// Compile the patterns of `filter`.
+    pub fn new(filter: &PathFilter) -> Result<Self, ThanixError> {
+        let paths = |patterns: &[String]| {
+            patterns
+                .iter()
+                .map(|p| PathPattern::new(p))
+                .collect::<Result<Vec<_>, _>>()
+        };
+        let regexes = |patterns: &[String]| {
+            patterns
+                .iter()
+                .map(|p| {
+                    Regex::new(p).map_err(|source| ThanixError::Regex {
+                        pattern: p.clone(),
+                        source,
+                    })
+                })
+                .collect::<Result<Vec<_>, _>>()
+        };
+        Ok(Self {
+            include: paths(&filter.include)?,
+            exclude: paths(&filter.exclude)?,
+            include_tags: filter.include_tags.clone(),
+            exclude_tags: filter.exclude_tags.clone(),
+            include_operations: regexes(&filter.include_operations)?,
+            exclude_operations: regexes(&filter.exclude_operations)?,
+        })
+    }
+
+    /// Get the part of the path item at `path` which should be generated.
+    ///
+    /// # Parameters
+    ///
+    /// * `path: &str` - The path of the item, e.g. `/api/dcim/devices/`.
+    /// * `item: &PathItem` - The operations of the path.
+    ///
+    /// # Returns
+    ///
+    /// * `Option<PathItem>` - The item with all filtered operations removed, or `None` if none
+    ///   of them is left.
+    pub fn path_item(&self, path: &str, item: &PathItem) -> Option<PathItem> {
+        if !self.include.is_empty() && !self.include.iter().any(|p| p.matches(path)) {
+            return None;
+        }
+        if self.exclude.iter().any(|p| p.matches(path)) {
+            return None;
+        }
+
+        let mut item = item.clone();
+        let operations = [
+            (&mut item.get, "get"),
+            (&mut item.put, "put"),
+            (&mut item.post, "post"),
+            (&mut item.delete, "delete"),
+            (&mut item.options, "options"),
+            (&mut item.head, "head"),
+            (&mut item.patch, "patch"),
+            (&mut item.trace, "trace"),
+        ];
+        let mut any = false;
+        for (op, method) in operations {
+            if op.as_ref().is_some_and(|op| !self.keeps(path, method, op)) {
+                *op = None;
+            }
+            any |= op.is_some();
+        }
+        any.then_some(item)
+    }
+
+    /// Check whether the tags and `operationId` of an operation are selected.
+    fn keeps(&self, path: &str, method: &str, op: &Operation) -> bool {
+        let has_tag = |tags: &[String]| op.tags.iter().any(|t| tags.contains(t));
+        if !self.include_tags.is_empty() && !has_tag(&self.include_tags) {
+            return false;
+        }
+        if has_tag(&self.exclude_tags) {
+            return false;
+        }
+
+        // Operations without an id are matched by the name of their function.
+        let id = op
+            .operation_id
+            .clone()
+            .unwrap_or_else(|| pathgen::make_fn_name_from_path(path) + "_" + method);
+        (self.include_operations.is_empty()
+            || self.include_operations.iter().any(|r| r.is_match(&id)))
+            && !self.exclude_operations.iter().any(|r| r.is_match(&id))
+    }
+}
+
+/// Remove all component schemas which aren't used by any of `items`, even indirectly.
+///
+/// All references but the ones to component schemas must already be resolved, see
+/// [`resolve_refs`](crate::resolve_refs).
+pub fn prune_schemas<'a>(
+    components: &mut Components,
+    items: impl IntoIterator<Item = &'a PathItem>,
+) {
+    let used = used_schemas(components, items);
+    components.schemas.retain(|name, _| used.contains(name));
+}
+
+/// Get the names of all component schemas used by any of `items`, even indirectly.
+pub fn used_schemas<'a>(
+    components: &Components,
+    items: impl IntoIterator<Item = &'a PathItem>,
+) -> BTreeSet<String> {
+    let mut names = Vec::new();
+    for item in items {
+        if let Ok(value) = serde_yaml::to_value(item) {
+            schema_refs(&value, &mut names);
+        }
+    }
+    reachable_schemas(components, names)
+}
+
+/// Get the names of all component schemas reachable from the ones in `names`, including them.
+pub fn reachable_schemas(components: &Components, mut names: Vec<String>) -> BTreeSet<String> {
+    let mut used = BTreeSet::new();
+    while let Some(name) = names.pop() {
+        if !used.insert(name.clone()) {
+            continue;
+        }
+        if let Some(Ok(value)) = components.schemas.get(&name).map(serde_yaml::to_value) {
+            schema_refs(&value, &mut names);
+        }
+    }
+    used
+}
+
+/// Collect the names of all component schemas referenced within `value`.
+pub fn schema_refs(value: &Value, names: &mut Vec<String>) {
+    match value {
+        Value::Mapping(map) => {
+            for (key, v) in map {
+                match (key.as_str(), v) {
+                    (Some("$ref"), Value::String(reference)) => {
+                        if let Some(name) = reference.strip_prefix("#/components/schemas/") {
+                            names.push(name.to_owned());
+                        }
+                    }
+                    // Discriminator mappings name their schemas without a `$ref`.
+                    (Some("discriminator"), _) => {
+                        let mapping = v.get("mapping").and_then(Value::as_mapping);
+                        for target in mapping
+                            .into_iter()
+                            .flatten()
+                            .filter_map(|(_, t)| t.as_str())
+                        {
+                            let name = target
+                                .strip_prefix("#/components/schemas/")
+                                .unwrap_or(target);
+                            names.push(name.to_owned());
+                        }
+                    }
+                    _ => schema_refs(v, names),
+                }
+            }
+        }
+        Value::Sequence(items) => {
+            for v in items {
+                schema_refs(v, names);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use openapiv3::OpenAPI;
+
+    use super::*;
+
+    static SPEC: &str = "openapi: 3.0.3\n\
+        info: {title: Test, version: '1'}\n\
+        paths:\n\
+        \x20 /api/dcim/devices/:\n\
+        \x20   get:\n\
+        \x20     operationId: dcim_devices_list\n\
+        \x20     tags: [dcim]\n\
+        \x20     responses: {'200': {description: OK, content: {application/json: {schema: {$ref: '#/components/schemas/Device'}}}}}\n\
+        \x20   delete:\n\
+        \x20     operationId: dcim_devices_bulk_destroy\n\
+        \x20     tags: [dcim]\n\
+        \x20     responses: {'204': {description: Deleted}}\n\
+        \x20 /api/ipam/prefixes/:\n\
+        \x20   get:\n\
+        \x20     operationId: ipam_prefixes_list\n\
+        \x20     tags: [ipam]\n\
+        \x20     responses: {'200': {description: OK, content: {application/json: {schema: {$ref: '#/components/schemas/Prefix'}}}}}\n\
+        components:\n\
+        \x20 schemas:\n\
+        \x20   Device:\n\
+        \x20     type: object\n\
+        \x20     properties: {tenant: {$ref: '#/components/schemas/Tenant'}, owner: {$ref: '#/components/schemas/Owner'}}\n\
+        \x20   Owner:\n\
+        \x20     oneOf: [{type: string}, {type: integer}]\n\
+        \x20     discriminator: {propertyName: kind, mapping: {user: User}}\n\
+        \x20   User: {type: object, properties: {name: {type: string}}}\n\
+        \x20   Tenant: {type: object, properties: {name: {type: string}}}\n\
+        \x20   Prefix: {type: object, properties: {prefix: {type: string}}}\n";
+
+    fn filtered(filter: PathFilter) -> Vec<(String, Vec<String>)> {
+        let api: OpenAPI = serde_yaml::from_str(SPEC).unwrap();
+        let filter = OperationFilter::new(&filter).unwrap();
+        api.paths
+            .iter()
+            .filter_map(|(path, item)| {
+                let item = filter.path_item(path, item.as_item()?)?;
+                let ops = item.iter().map(|(method, _)| method.to_owned()).collect();
+                Some((path.clone(), ops))
+            })
+            .collect()
+    }
+
+    fn selects(filter: &PathFilter, path: &str) -> bool {
+        let item: PathItem =
+            serde_yaml::from_str("get: {operationId: test, responses: {}}").unwrap();
+        let filter = OperationFilter::new(filter).unwrap();
+        filter.path_item(path, &item).is_some()
+    }
+
+    #[test]
+    fn test_filter_paths() {
+        let filter = PathFilter {
+            include: vec!["/api/dcim/".to_owned()],
+            exclude: vec!["/api/dcim/cables/".to_owned()],
+            ..Default::default()
+        };
+        assert!(selects(&filter, "/api/dcim/devices/"));
+        assert!(!selects(&filter, "/api/dcim/cables/"));
+        assert!(!selects(&filter, "/api/ipam/prefixes/"));
+        assert!(selects(&PathFilter::default(), "/api/ipam/prefixes/"));
+    }
+
+    #[test]
+    fn test_filter_paths_glob() {
+        let filter = PathFilter {
+            include: vec!["/api/dcim/**".to_owned(), "/api/*/tags/".to_owned()],
+            ..Default::default()
+        };
+        assert!(selects(&filter, "/api/dcim/devices/{id}/"));
+        assert!(selects(&filter, "/api/extras/tags/"));
+        assert!(!selects(&filter, "/api/extras/tags/{id}/"));
+        assert!(!selects(&filter, "/api/ipam/prefixes/"));
+    }
+
+    #[test]
+    fn test_filter_tags() {
+        let result = filtered(PathFilter {
+            include_tags: vec!["ipam".to_owned()],
+            ..Default::default()
+        });
+        assert_eq!(
+            result,
+            [("/api/ipam/prefixes/".to_owned(), vec!["get".to_owned()])]
+        );
+    }
+
+    #[test]
+    fn test_filter_operations() {
+        let result = filtered(PathFilter {
+            include: vec!["/api/dcim/**".to_owned()],
+            exclude_operations: vec!["_bulk_".to_owned()],
+            ..Default::default()
+        });
+        assert_eq!(
+            result,
+            [("/api/dcim/devices/".to_owned(), vec!["get".to_owned()])]
+        );
+    }
+
+    #[test]
+    fn test_filter_invalid() {
+        let filter = PathFilter {
+            include_operations: vec!["dcim_(".to_owned()],
+            ..Default::default()
+        };
+        assert!(matches!(
+            OperationFilter::new(&filter),
+            Err(ThanixError::Regex { .. })
+        ));
+    }
+
+    #[test]
+    fn test_filter_invalid_glob() {
+        let filter = PathFilter {
+            include: vec!["/api/[dcim/**".to_owned()],
+            ..Default::default()
+        };
+        assert!(matches!(
+            OperationFilter::new(&filter),
+            Err(ThanixError::Pattern { .. })
+        ));
+    }
+
+    #[test]
+    fn test_prune_schemas() {
+        let api: OpenAPI = serde_yaml::from_str(SPEC).unwrap();
+        let mut components = api.components.unwrap();
+        let devices = api.paths.paths["/api/dcim/devices/"].as_item().unwrap();
+        prune_schemas(&mut components, [devices]);
+
+        let names: Vec<&String> = components.schemas.keys().collect();
+        assert_eq!(names, ["Device", "Owner", "User", "Tenant"]);
+    }
+}
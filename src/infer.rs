@@ -0,0 +1,426 @@
+//! Infer workaround overrides from recorded API responses.
+//!
+//! Responses are recorded as JSON files in a directory, named after the operation which returned
+//! them, either as `<operation>.json` or as any number of `<operation>/*.json`. The operation is
+//! its `operationId`, or the function name Thanix would generate for it otherwise.
+
+use std::{
+    borrow::Borrow,
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use convert_case::{Case, Casing};
+use openapiv3::{
+    Components, OpenAPI, ReferenceOr, Responses, Schema, SchemaKind, StatusCode, Type,
+};
+use serde_json::Value;
+
+use crate::error::ThanixError;
+use crate::{pathgen, resolve};
+
+/// How a recorded field deviated from the schema.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Deviation {
+    /// A required, non-nullable field was `null`.
+    Null,
+    /// A required field was absent.
+    Missing,
+    /// A field had a value of another type than the schema states.
+    WrongType,
+}
+
+impl fmt::Display for Deviation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Null => "null",
+            Self::Missing => "missing",
+            Self::WrongType => "wrong type",
+        })
+    }
+}
+
+/// The deviations found in a set of recorded responses.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Overrides {
+    /// Deviations keyed by `Schema.field`, as understood by `--unsanitary`.
+    pub fields: BTreeMap<String, BTreeSet<Deviation>>,
+    /// Number of responses which have been checked.
+    pub responses: usize,
+    /// Recordings which couldn't be checked.
+    pub warnings: Vec<String>,
+}
+
+impl Overrides {
+    /// Render the overrides as a `thanix.toml` which enables the workaround for the affected fields.
+    ///
+    /// Fields with a wrong type are only listed as comments, as making them optional doesn't help.
+    pub fn to_toml(&self) -> String {
+        let mut result = format!(
+            "# Generated by `thanix infer-overrides` from {} recorded responses.\n\
+             # Use it with `thanix --config`, or merge it into your `thanix.toml`.\n\n\
+             [workaround]\nenabled = true\nunsanitary = [\n",
+            self.responses
+        );
+        let mut wrong_types = Vec::new();
+        for (field, deviations) in &self.fields {
+            if deviations.contains(&Deviation::WrongType) {
+                wrong_types.push(field);
+            }
+            let fixable: Vec<String> = deviations
+                .iter()
+                .filter(|d| **d != Deviation::WrongType)
+                .map(Deviation::to_string)
+                .collect();
+            if !fixable.is_empty() {
+                result += &format!("    {:?}, # {}\n", field, fixable.join(", "));
+            }
+        }
+        result += "]\n";
+
+        if !wrong_types.is_empty() {
+            result += "\n# These fields had values of the wrong type, which making them optional doesn't fix.\n\
+                       # Consider a `[type-mappings]` entry or fixing the schema instead:\n";
+            for field in wrong_types {
+                result += &format!("# {:?}\n", field);
+            }
+        }
+        result
+    }
+
+    fn record(&mut self, schema: &str, field: &str, deviation: Deviation) {
+        self.fields
+            .entry(format!("{}.{}", schema, field))
+            .or_default()
+            .insert(deviation);
+    }
+}
+
+/// Check all responses recorded in `responses_dir` against the schema of their operation.
+///
+/// # Parameters
+///
+/// * `api: &OpenAPI` - The schema the responses should follow.
+/// * `responses_dir: &Path` - The directory with the recorded responses.
+///
+/// # Returns
+///
+/// * `Result<Overrides, ThanixError>` - The deviations, or an error if a recording couldn't be read.
+pub fn infer_overrides(api: &OpenAPI, responses_dir: &Path) -> Result<Overrides, ThanixError> {
+    let api = &resolve::resolve_refs(api)?;
+    // Operations keyed by the name of their generated function.
+    let mut operations = HashMap::new();
+    for (path, item) in &api.paths.paths {
+        let ReferenceOr::Item(item) = item else {
+            continue;
+        };
+        for (method, op) in item.iter() {
+            let fn_name = op
+                .operation_id
+                .clone()
+                .unwrap_or(pathgen::make_fn_name_from_path(path) + "_" + method);
+            operations.insert(fn_name, op);
+        }
+    }
+
+    let components = api.components.clone().unwrap_or_default();
+    let mut overrides = Overrides::default();
+    for (operation, path) in recordings(responses_dir)? {
+        let Some(op) = operations.get(&operation) else {
+            overrides.warnings.push(format!(
+                "{}: there is no operation `{}`",
+                path.display(),
+                operation
+            ));
+            continue;
+        };
+        let Some((status, schema)) = success_schema(&op.responses) else {
+            overrides.warnings.push(format!(
+                "{}: operation `{}` has no JSON success response",
+                path.display(),
+                operation
+            ));
+            continue;
+        };
+        let contents = fs::read_to_string(&path).map_err(|source| ThanixError::Io {
+            path: path.clone(),
+            source,
+        })?;
+        let value: Value = match serde_json::from_str(&contents) {
+            Ok(x) => x,
+            Err(e) => {
+                overrides
+                    .warnings
+                    .push(format!("{}: invalid JSON: {}", path.display(), e));
+                continue;
+            }
+        };
+
+        // Inline response schemas are named like `pathgen` hoists them.
+        let hint = format!("{}Response{}", operation.to_case(Case::Pascal), status);
+        let mut validator = Validator {
+            components: &components,
+            overrides: &mut overrides,
+        };
+        validator.check(schema, &value, &hint);
+        overrides.responses += 1;
+    }
+    Ok(overrides)
+}
+
+/// List the recordings in `dir` with the operation they belong to, in a stable order.
+fn recordings(dir: &Path) -> Result<Vec<(String, PathBuf)>, ThanixError> {
+    let read_dir = |dir: &Path| -> Result<Vec<PathBuf>, ThanixError> {
+        let io_error = |source| ThanixError::Io {
+            path: dir.to_owned(),
+            source,
+        };
+        let mut paths = fs::read_dir(dir)
+            .map_err(io_error)?
+            .map(|entry| entry.map(|e| e.path()))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(io_error)?;
+        paths.sort();
+        Ok(paths)
+    };
+    let is_json = |path: &Path| path.is_file() && path.extension().is_some_and(|e| e == "json");
+
+    let mut result = Vec::new();
+    for path in read_dir(dir)? {
+        if path.is_dir() {
+            let Some(operation) = path.file_name() else {
+                continue;
+            };
+            let operation = operation.to_string_lossy().into_owned();
+            for file in read_dir(&path)? {
+                if is_json(&file) {
+                    result.push((operation.clone(), file));
+                }
+            }
+        } else if is_json(&path)
+            && let Some(operation) = path.file_stem()
+        {
+            result.push((operation.to_string_lossy().into_owned(), path));
+        }
+    }
+    Ok(result)
+}
+
+/// Find the JSON schema of the first `2XX` response.
+fn success_schema(responses: &Responses) -> Option<(String, &ReferenceOr<Schema>)> {
+    responses.responses.iter().find_map(|(status, response)| {
+        let status = match status {
+            StatusCode::Code(code @ 200..=299) => code.to_string(),
+            StatusCode::Range(2) => "2XX".to_owned(),
+            _ => return None,
+        };
+        let schema = response
+            .as_item()?
+            .content
+            .get("application/json")?
+            .schema
+            .as_ref()?;
+        Some((status, schema))
+    })
+}
+
+/// Walks a recorded value along its schema.
+struct Validator<'a> {
+    components: &'a Components,
+    overrides: &'a mut Overrides,
+}
+
+impl Validator<'_> {
+    /// Check `value` against `schema`, which is generated as `hint` unless it's a reference.
+    fn check<S: Borrow<Schema>>(&mut self, schema: &ReferenceOr<S>, value: &Value, hint: &str) {
+        let (schema, name) = match schema {
+            ReferenceOr::Item(x) => (x.borrow(), hint.to_owned()),
+            ReferenceOr::Reference { reference } => match resolve(self.components, reference) {
+                Some(x) => x,
+                None => return,
+            },
+        };
+        self.check_schema(schema, value, &name);
+    }
+
+    /// Check `value` against `schema`, which is generated as `name`.
+    fn check_schema(&mut self, schema: &Schema, value: &Value, name: &str) {
+        match &schema.schema_kind {
+            SchemaKind::Type(Type::Object(obj)) => {
+                let Value::Object(fields) = value else {
+                    return;
+                };
+                for (prop_name, prop) in &obj.properties {
+                    let required = obj.required.contains(prop_name);
+                    // Like `structgen`, only inline schemas can be nullable.
+                    let nullable =
+                        matches!(prop, ReferenceOr::Item(item) if item.schema_data.nullable);
+                    match fields.get(prop_name) {
+                        None if required => {
+                            self.overrides.record(name, prop_name, Deviation::Missing)
+                        }
+                        None => {}
+                        Some(Value::Null) if required && !nullable => {
+                            self.overrides.record(name, prop_name, Deviation::Null)
+                        }
+                        Some(Value::Null) => {}
+                        Some(field) if !self.matches_type(prop, field) => {
+                            self.overrides.record(name, prop_name, Deviation::WrongType)
+                        }
+                        Some(field) => self.check(
+                            prop,
+                            field,
+                            &format!("{}{}", name, prop_name.to_case(Case::Pascal)),
+                        ),
+                    }
+                }
+            }
+            SchemaKind::Type(Type::Array(array)) => {
+                if let (Some(items), Value::Array(values)) = (&array.items, value) {
+                    for item in values {
+                        self.check(items, item, name);
+                    }
+                }
+            }
+            // The members are merged into a single struct.
+            SchemaKind::AllOf { all_of } => {
+                for member in all_of {
+                    let member = match member {
+                        ReferenceOr::Item(x) => x,
+                        ReferenceOr::Reference { reference } => {
+                            match resolve(self.components, reference) {
+                                Some((x, _)) => x,
+                                None => continue,
+                            }
+                        }
+                    };
+                    self.check_schema(member, value, name);
+                }
+            }
+            // We can't know which member of a `oneOf` or `anyOf` was meant.
+            _ => {}
+        }
+    }
+
+    /// Check whether `value` has the type stated by `schema`.
+    fn matches_type<S: Borrow<Schema>>(&self, schema: &ReferenceOr<S>, value: &Value) -> bool {
+        let schema = match schema {
+            ReferenceOr::Item(x) => x.borrow(),
+            ReferenceOr::Reference { reference } => match resolve(self.components, reference) {
+                Some((x, _)) => x,
+                None => return true,
+            },
+        };
+        match &schema.schema_kind {
+            SchemaKind::Type(Type::String(_)) => value.is_string(),
+            SchemaKind::Type(Type::Number(_)) => value.is_number(),
+            SchemaKind::Type(Type::Integer(_)) => value.is_i64() || value.is_u64(),
+            SchemaKind::Type(Type::Boolean(_)) => value.is_boolean(),
+            SchemaKind::Type(Type::Object(_)) => value.is_object(),
+            SchemaKind::Type(Type::Array(_)) => value.is_array(),
+            _ => true,
+        }
+    }
+}
+
+/// Look up a component schema and its name.
+fn resolve<'a>(components: &'a Components, reference: &str) -> Option<(&'a Schema, String)> {
+    let name = reference.strip_prefix("#/components/schemas/")?;
+    let schema = components.schemas.get(name)?.as_item()?;
+    Some((schema, name.to_owned()))
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    static SPEC: &str = "openapi: 3.0.3\n\
+        info: {title: Test, version: '1'}\n\
+        paths:\n\
+        \x20 /api/dcim/devices/:\n\
+        \x20   get:\n\
+        \x20     operationId: dcim_devices_list\n\
+        \x20     responses:\n\
+        \x20       '200':\n\
+        \x20         description: OK\n\
+        \x20         content:\n\
+        \x20           application/json:\n\
+        \x20             schema:\n\
+        \x20               type: array\n\
+        \x20               items: {$ref: '#/components/schemas/Device'}\n\
+        components:\n\
+        \x20 schemas:\n\
+        \x20   Device:\n\
+        \x20     type: object\n\
+        \x20     required: [id, name, serial, site]\n\
+        \x20     properties:\n\
+        \x20       id: {type: integer}\n\
+        \x20       name: {type: string}\n\
+        \x20       serial: {type: string}\n\
+        \x20       comments: {type: string}\n\
+        \x20       site:\n\
+        \x20         type: object\n\
+        \x20         required: [slug]\n\
+        \x20         properties:\n\
+        \x20           slug: {type: string}\n";
+
+    #[test]
+    fn test_infer_overrides() {
+        let api: OpenAPI = serde_yaml::from_str(SPEC).unwrap();
+        let dir = TempDir::new().unwrap();
+        fs::write(
+            dir.path().join("dcim_devices_list.json"),
+            r#"[
+                {"id": 1, "name": null, "serial": "abc", "comments": null, "site": {"slug": null}},
+                {"id": 2, "name": "b", "serial": 5, "site": {}}
+            ]"#,
+        )
+        .unwrap();
+        fs::create_dir(dir.path().join("unknown_op")).unwrap();
+        fs::write(dir.path().join("unknown_op/1.json"), "{}").unwrap();
+
+        let overrides = infer_overrides(&api, dir.path()).unwrap();
+        assert_eq!(overrides.responses, 1);
+        assert_eq!(overrides.warnings.len(), 1);
+        assert!(overrides.warnings[0].contains("no operation `unknown_op`"));
+
+        let fields: Vec<(&str, Vec<Deviation>)> = overrides
+            .fields
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.iter().copied().collect()))
+            .collect();
+        assert_eq!(
+            fields,
+            vec![
+                ("Device.name", vec![Deviation::Null]),
+                ("Device.serial", vec![Deviation::WrongType]),
+                ("DeviceSite.slug", vec![Deviation::Null, Deviation::Missing]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_to_toml() {
+        let mut overrides = Overrides {
+            responses: 2,
+            ..Default::default()
+        };
+        overrides.record("Device", "name", Deviation::Null);
+        overrides.record("Device", "name", Deviation::Missing);
+        overrides.record("Device", "serial", Deviation::WrongType);
+
+        let toml = overrides.to_toml();
+        assert!(toml.contains("from 2 recorded responses"));
+        assert!(toml.contains("    \"Device.name\", # null, missing\n"));
+        assert!(toml.contains("# \"Device.serial\"\n"));
+        assert!(!toml.contains("    \"Device.serial\""));
+
+        // The output is a valid configuration file.
+        let file: crate::ConfigFile = toml::from_str(&toml).unwrap();
+        assert!(file.workaround.enabled);
+        assert_eq!(file.workaround.unsanitary, ["Device.name"]);
+    }
+}
@@ -0,0 +1,223 @@
+//! Read plain JSON Schema documents, e.g. for NetBox config contexts, to generate types from.
+//!
+//! Every document becomes a component schema of an otherwise empty OpenAPI document, named after
+//! its `title` or its file. Its `definitions` and `$defs` become component schemas of their own,
+//! and newer JSON Schema keywords are converted like the ones of OpenAPI 3.1 schemas.
+
+use std::path::Path;
+
+use convert_case::{Case, Casing};
+use openapiv3::Components;
+use serde_yaml::{Mapping, Value};
+
+use crate::error::ThanixError;
+use crate::{bundle, openapi31, resolve};
+
+/// Read the JSON Schema documents at `paths` into the components of an OpenAPI document.
+///
+/// Like with [`read_spec`](crate::read_spec), the documents may be written in YAML or JSON and
+/// reference other files. A path of `-` reads a document from stdin.
+///
+/// # Parameters
+///
+/// * `paths: &[P]` - The JSON Schema documents to read.
+///
+/// # Returns
+///
+/// * `Result<Components, ThanixError>` - The component schemas of all documents, ready to be
+///   passed to [`Generator::generate_types`](crate::Generator::generate_types).
+pub fn read_json_schemas<P: AsRef<Path>>(paths: &[P]) -> Result<Components, ThanixError> {
+    let mut schemas = Mapping::new();
+    for path in paths {
+        let path = path.as_ref();
+        let mut schema = bundle::read_document(path)?;
+        let name = schema_name(&schema, path);
+        prepare(&mut schema, &name);
+
+        let mut doc = Mapping::new();
+        doc.insert(
+            "components".into(),
+            Value::Mapping(Mapping::from_iter([(
+                "schemas".into(),
+                Value::Mapping(Mapping::from_iter([(name.as_str().into(), schema)])),
+            )])),
+        );
+        let doc = bundle::bundle(Value::Mapping(doc), path)?;
+
+        // Schemas imported from other files may be shared by several documents.
+        let bundled = doc["components"]["schemas"].as_mapping().cloned();
+        for (key, schema) in bundled.into_iter().flatten() {
+            match schemas.get(&key) {
+                Some(existing) if *existing != schema => {
+                    return Err(ThanixError::schema(
+                        &["components", "schemas", key.as_str().unwrap_or_default()],
+                        format!(
+                            "defined differently by {}, give the schema another `title`",
+                            path.display()
+                        ),
+                    ));
+                }
+                _ => {
+                    schemas.insert(key, schema);
+                }
+            }
+        }
+    }
+
+    // The documents are converted like the schemas of an OpenAPI 3.1 document.
+    let mut doc = Mapping::new();
+    doc.insert("openapi".into(), "3.1.0".into());
+    doc.insert(
+        "components".into(),
+        Value::Mapping(Mapping::from_iter([(
+            "schemas".into(),
+            Value::Mapping(schemas),
+        )])),
+    );
+    let mut doc = Value::Mapping(doc);
+    openapi31::downgrade(&mut doc)?;
+    let doc = resolve::resolve_document(doc)?;
+    serde_yaml::from_value(doc["components"].clone())
+        .map_err(|e| ThanixError::schema(&["components"], e.to_string()))
+}
+
+/// Name the type of a document after its `title`, or its file if it doesn't have a usable one.
+fn schema_name(schema: &Value, path: &Path) -> String {
+    let title = schema
+        .get("title")
+        .and_then(Value::as_str)
+        .map(|title| title.to_case(Case::Pascal));
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_case(Case::Pascal))
+        .filter(|_| path != Path::new("-"));
+    title
+        .into_iter()
+        .chain(stem)
+        .find(|name| {
+            name.starts_with(|c: char| c.is_ascii_alphabetic())
+                && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        })
+        .unwrap_or_else(|| "Schema".to_owned())
+}
+
+/// Move the `definitions` of a draft 7 document to `$defs`, and rewrite the local references of
+/// the document to where it ends up, at `#/components/schemas/<name>`.
+fn prepare(schema: &mut Value, name: &str) {
+    if let Some(map) = schema.as_mapping_mut()
+        && let Some(Value::Mapping(definitions)) = map.remove("definitions")
+    {
+        let defs = map
+            .entry("$defs".into())
+            .or_insert_with(|| Value::Mapping(Mapping::new()));
+        if let Value::Mapping(defs) = defs {
+            for (key, value) in definitions {
+                defs.entry(key).or_insert(value);
+            }
+        }
+    }
+    rewrite_refs(schema, &format!("#/components/schemas/{}", name));
+}
+
+fn rewrite_refs(value: &mut Value, prefix: &str) {
+    match value {
+        Value::Mapping(map) => {
+            if let Some(Value::String(reference)) = map.get_mut("$ref")
+                && let Some(rest) = reference.strip_prefix('#')
+            {
+                let rest = match rest.strip_prefix("/definitions/") {
+                    Some(definition) => format!("/$defs/{}", definition),
+                    None => rest.to_owned(),
+                };
+                *reference = format!("{}{}", prefix, rest);
+            }
+            for (_, v) in map.iter_mut() {
+                rewrite_refs(v, prefix);
+            }
+        }
+        Value::Sequence(items) => {
+            for v in items {
+                rewrite_refs(v, prefix);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use openapiv3::ReferenceOr;
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn test_read_json_schemas() {
+        let dir = TempDir::new().unwrap();
+        fs::write(
+            dir.path().join("ntp.json"),
+            r##"{
+                "$schema": "http://json-schema.org/draft-07/schema#",
+                "title": "NTP config",
+                "type": "object",
+                "properties": {
+                    "servers": {"type": "array", "items": {"$ref": "#/definitions/Server"}},
+                    "timezone": {"type": ["string", "null"]},
+                    "self": {"$ref": "#"}
+                },
+                "definitions": {
+                    "Server": {"type": "object", "properties": {"host": {"type": "string"}}}
+                }
+            }"##,
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("syslog.yaml"),
+            "type: object\nproperties:\n  level: {const: info}\n",
+        )
+        .unwrap();
+
+        let components =
+            read_json_schemas(&[dir.path().join("ntp.json"), dir.path().join("syslog.yaml")])
+                .unwrap();
+        let names: Vec<&String> = components.schemas.keys().collect();
+        assert_eq!(names, ["NtpConfig", "Syslog", "Server"]);
+
+        let ReferenceOr::Item(ntp) = &components.schemas["NtpConfig"] else {
+            panic!("NtpConfig is a reference");
+        };
+        let ntp = serde_yaml::to_value(ntp).unwrap();
+        let properties = &ntp["properties"];
+        assert_eq!(
+            properties["servers"]["items"]["$ref"],
+            "#/components/schemas/Server"
+        );
+        assert_eq!(properties["self"]["$ref"], "#/components/schemas/NtpConfig");
+        assert_eq!(properties["timezone"]["nullable"], true);
+    }
+
+    #[test]
+    fn test_read_json_schemas_conflict() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("a.yaml"), "title: Same\ntype: string\n").unwrap();
+        fs::write(dir.path().join("b.yaml"), "title: Same\ntype: integer\n").unwrap();
+
+        let err =
+            read_json_schemas(&[dir.path().join("a.yaml"), dir.path().join("b.yaml")]).unwrap_err();
+        assert!(err.to_string().starts_with("#/components/schemas/Same: "));
+    }
+
+    #[test]
+    fn test_schema_name() {
+        let schema = serde_yaml::from_str("title: config context").unwrap();
+        assert_eq!(schema_name(&schema, Path::new("x.json")), "ConfigContext");
+        let schema = serde_yaml::from_str("title: '42'").unwrap();
+        assert_eq!(
+            schema_name(&schema, Path::new("site-config.json")),
+            "SiteConfig"
+        );
+        assert_eq!(schema_name(&Value::Null, Path::new("-")), "Schema");
+    }
+}
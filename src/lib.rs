@@ -0,0 +1,36 @@
+//! Generate Rust API client crates from OpenAPI schemas.
+//!
+//! ```no_run
+//! use thanix::{Generator, GeneratorConfig};
+//!
+//! let config = GeneratorConfig::builder().crate_name("netbox").async_mode(true).build();
+//! let output = Generator::new(config).generate_from_file("netbox.yaml")?;
+//! output.write("netbox")?;
+//! # Ok::<(), thanix::ThanixError>(())
+//! ```
+
+mod auth;
+mod bindgen;
+mod bundle;
+mod config;
+pub mod error;
+mod filter;
+mod infer;
+mod jsonschema;
+mod openapi31;
+mod pathgen;
+mod resolve;
+mod split;
+mod structgen;
+mod swagger2;
+mod util;
+
+pub use bindgen::{DEFAULT_CRATE_NAME, GeneratedCrate, Generator, read_spec};
+pub use config::{
+    ClientSection, ConfigFile, GeneratorConfig, GeneratorConfigBuilder, GeneratorSection,
+    PackageInfo, PathFilter, WorkaroundSection,
+};
+pub use error::ThanixError;
+pub use infer::{Deviation, Overrides, infer_overrides};
+pub use jsonschema::read_json_schemas;
+pub use resolve::resolve_refs;
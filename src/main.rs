@@ -1,41 +1,375 @@
-mod bindgen;
-mod pathgen;
-mod structgen;
-mod util;
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
 
-use std::path::PathBuf;
+use clap::{Parser, Subcommand, ValueEnum};
+use thanix::{
+    ConfigFile, Generator, GeneratorConfig, ThanixError, infer_overrides, read_json_schemas,
+    read_spec,
+};
 
-use clap::Parser;
+/// Configuration file which is read if `--config` isn't given and it exists.
+const DEFAULT_CONFIG_FILE: &str = "thanix.toml";
 
 /// The arguments that Thanix expects to get given via the cli.
 #[derive(Parser, Debug)]
-#[command(author, version, about, long_about=None)]
+#[command(author, version, about, long_about=None, args_conflicts_with_subcommands = true)]
 struct Args {
-    #[arg(short, long, default_value = "output")]
-    output: PathBuf,
-    /// Path to a YAML schema file.
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// Directory to write the crate into. Defaults to `output`.
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+    /// Path to a YAML or JSON schema file, or `-` to read it from stdin.
     input: Option<String>,
+    /// Write the crate to stdout instead of the output directory.
+    #[arg(long, value_name = "FORMAT")]
+    stdout: Option<StdoutFormat>,
+    /// Path to a `thanix.toml` project configuration file.
+    /// Flags given on the command line take precedence over the values in it.
+    #[arg(short, long)]
+    config: Option<PathBuf>,
     /// Enable Workaround mode.
     /// Creates opinionated NetBox API client.
     /// Can help with unsanitary response data crashing deserialization by making API object fields optional, even though
     /// the YAML might state otherwise.
-    #[arg(short, long, action = clap::ArgAction::SetTrue)]
+    #[arg(short, long, action = clap::ArgAction::SetTrue, overrides_with = "no_workaround")]
     workaround: bool,
+    /// Disable workaround mode, even if the configuration file enables it.
+    #[arg(long, action = clap::ArgAction::SetTrue, overrides_with = "workaround")]
+    no_workaround: bool,
+    /// Loosen only the schemas or fields matching this pattern in workaround mode, e.g. `Interface`,
+    /// `*Interface*` or `Device.primary_ip*`. Patterns match whole names, ignoring case, so use
+    /// `*interface*` to match every name containing `interface`. Can be given multiple times.
+    /// Without it, every response struct is loosened.
+    #[arg(short, long, value_name = "SCHEMA[.FIELD]")]
+    unsanitary: Vec<String>,
+    /// Additionally generate `async` API functions over `reqwest::Client`.
+    /// These are gated behind the `async` feature of the generated crate, the blocking functions
+    /// behind the (default) `blocking` feature.
+    #[arg(short, long = "async", action = clap::ArgAction::SetTrue, overrides_with = "no_async")]
+    async_mode: bool,
+    /// Only generate blocking API functions, even if the configuration file enables `async`.
+    #[arg(long, action = clap::ArgAction::SetTrue, overrides_with = "async_mode")]
+    no_async: bool,
+    /// Add an `Unknown` variant to all generated enums.
+    /// Values which are not listed in the schema are deserialized into it instead of failing.
+    #[arg(short, long, action = clap::ArgAction::SetTrue, overrides_with = "no_enum_fallback")]
+    enum_fallback: bool,
+    /// Don't add an `Unknown` variant, even if the configuration file enables it.
+    #[arg(long, action = clap::ArgAction::SetTrue, overrides_with = "enum_fallback")]
+    no_enum_fallback: bool,
+    /// Use a custom Rust type for a schema format, e.g. `date-time=String`.
+    /// Can be given multiple times and overrides the built-in mappings.
+    #[arg(short, long = "type-mapping", value_name = "FORMAT=TYPE", value_parser = parse_type_mapping)]
+    type_mappings: Vec<(String, String)>,
+    /// Generate maps as `BTreeMap` instead of `HashMap`, keeping their keys ordered.
+    #[arg(short, long, action = clap::ArgAction::SetTrue, overrides_with = "no_btree_maps")]
+    btree_maps: bool,
+    /// Generate maps as `HashMap`, even if the configuration file enables `btree-maps`.
+    #[arg(long, action = clap::ArgAction::SetTrue, overrides_with = "btree_maps")]
+    no_btree_maps: bool,
+    /// Only generate paths starting with this prefix or matching this glob, e.g. `/api/dcim/**`.
+    /// Can be given multiple times. Only the types used by the generated paths are kept.
+    #[arg(long, value_name = "PATTERN")]
+    include: Vec<String>,
+    /// Don't generate paths starting with this prefix or matching this glob.
+    #[arg(long, value_name = "PATTERN")]
+    exclude: Vec<String>,
+    /// Only generate operations with this tag, e.g. `dcim`. Can be given multiple times.
+    #[arg(long = "include-tag", value_name = "TAG")]
+    include_tags: Vec<String>,
+    /// Don't generate operations with this tag.
+    #[arg(long = "exclude-tag", value_name = "TAG")]
+    exclude_tags: Vec<String>,
+    /// Only generate operations whose `operationId` matches this regular expression.
+    #[arg(long = "include-operation", value_name = "REGEX")]
+    include_operations: Vec<String>,
+    /// Don't generate operations whose `operationId` matches this regular expression, e.g. `_bulk_`.
+    #[arg(long = "exclude-operation", value_name = "REGEX")]
+    exclude_operations: Vec<String>,
 }
 
-fn main() {
+/// How the crate is written to stdout.
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum StdoutFormat {
+    /// An uncompressed tarball with all files in a directory named after the crate.
+    Tar,
+    /// All files concatenated, each one starting with a `==> path <==` line.
+    Bundle,
+}
+
+/// Tasks besides generating a client.
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Check recorded API responses against the schema and write the fields which deviate from it
+    /// into a configuration file for `--workaround`.
+    InferOverrides {
+        /// Path to a YAML or JSON schema file, or `-` to read it from stdin.
+        input: String,
+        /// Directory with the recorded JSON responses, named `<operation>.json` or
+        /// `<operation>/*.json` after the `operationId` which returned them.
+        responses: PathBuf,
+        /// File to write the overrides to.
+        #[arg(short, long, default_value = "thanix-overrides.toml")]
+        output: PathBuf,
+    },
+    /// Generate only types, without any client code, from plain JSON Schema documents.
+    Types {
+        /// Paths to YAML or JSON Schema files, or `-` to read one from stdin.
+        #[arg(required = true)]
+        inputs: Vec<String>,
+        /// Directory to write the crate into, or the file to write the module into with
+        /// `--module`. Defaults to `output` or `types.rs`.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Write a single module to include in another crate, instead of a crate.
+        #[arg(short, long, action = clap::ArgAction::SetTrue)]
+        module: bool,
+        /// Add an `Unknown` variant to all generated enums.
+        #[arg(short, long, action = clap::ArgAction::SetTrue)]
+        enum_fallback: bool,
+        /// Use a custom Rust type for a schema format, e.g. `date-time=String`.
+        #[arg(short, long = "type-mapping", value_name = "FORMAT=TYPE", value_parser = parse_type_mapping)]
+        type_mappings: Vec<(String, String)>,
+        /// Generate maps as `BTreeMap` instead of `HashMap`.
+        #[arg(short, long, action = clap::ArgAction::SetTrue)]
+        btree_maps: bool,
+    },
+}
+
+/// Parse a `FORMAT=TYPE` pair given to `--type-mapping`.
+fn parse_type_mapping(input: &str) -> Result<(String, String), String> {
+    match input.split_once('=') {
+        Some((format, typ)) if !format.is_empty() && !typ.is_empty() => {
+            Ok((format.to_owned(), typ.to_owned()))
+        }
+        _ => Err(format!("expected FORMAT=TYPE, got `{}`", input)),
+    }
+}
+
+fn main() -> ExitCode {
     let args: Args = Args::parse();
 
+    // Messages go to stderr if stdout is taken by the crate itself.
+    let status = |message: &str| {
+        if args.stdout.is_some() {
+            eprintln!("{}", message);
+        } else {
+            println!("{}", message);
+        }
+    };
+
     // Welcome Message
-    println!(
+    status(&format!(
         "{} \n(c) The Nazara Project. (github.com/The-Nazara-Project)\n
         Licensed under the terms of the GPL-v3.0-License.\n\
         Check github.com/The-Nazara-Project/Thanix/LICENSE for more info.\n",
         include_str!("templates/ascii_art.template")
+    ));
+
+    match args.command {
+        Some(Command::InferOverrides {
+            input,
+            responses,
+            output,
+        }) => return run_infer_overrides(&input, &responses, &output),
+        Some(Command::Types {
+            inputs,
+            output,
+            module,
+            enum_fallback,
+            type_mappings,
+            btree_maps,
+        }) => {
+            let mut config = GeneratorConfig::builder()
+                .enum_fallback(enum_fallback)
+                .btree_maps(btree_maps);
+            for (format, typ) in type_mappings {
+                config = config.type_mapping(format, typ);
+            }
+            let mut config = config.build();
+            let output =
+                output.unwrap_or_else(|| PathBuf::from(if module { "types.rs" } else { "output" }));
+            if !module {
+                config.package.name = crate_name(&output);
+            }
+            return run_types(&inputs, &output, module, config);
+        }
+        None => {}
+    }
+
+    let file = match args.config {
+        Some(path) => ConfigFile::from_file(path),
+        None if Path::new(DEFAULT_CONFIG_FILE).exists() => {
+            ConfigFile::from_file(DEFAULT_CONFIG_FILE)
+        }
+        None => Ok(ConfigFile::default()),
+    };
+    let file = match file {
+        Ok(file) => file,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let Some(input) = args.input.or_else(|| file.input.clone()) else {
+        eprintln!("Error: You need to provide a YAML or JSON schema to generate from.");
+        return ExitCode::FAILURE;
+    };
+    let output_path = args
+        .output
+        .or_else(|| file.output.clone())
+        .unwrap_or_else(|| PathBuf::from("output"));
+
+    let mut config = file.builder().build();
+    // Flags only override the file if they are given.
+    let flag = |enable: bool, disable: bool, value: &mut bool| {
+        if enable || disable {
+            *value = enable;
+        }
+    };
+    flag(
+        args.workaround,
+        args.no_workaround,
+        &mut config.workaround_mode,
+    );
+    if !args.unsanitary.is_empty() {
+        config.unsanitary = args.unsanitary;
+    }
+    flag(args.async_mode, args.no_async, &mut config.async_mode);
+    flag(
+        args.enum_fallback,
+        args.no_enum_fallback,
+        &mut config.enum_fallback,
+    );
+    flag(args.btree_maps, args.no_btree_maps, &mut config.btree_maps);
+    config.type_mappings.extend(args.type_mappings);
+    config.filter.include.extend(args.include);
+    config.filter.exclude.extend(args.exclude);
+    config.filter.include_tags.extend(args.include_tags);
+    config.filter.exclude_tags.extend(args.exclude_tags);
+    config
+        .filter
+        .include_operations
+        .extend(args.include_operations);
+    config
+        .filter
+        .exclude_operations
+        .extend(args.exclude_operations);
+    if config.package.name.is_none() && args.stdout.is_none() {
+        config.package.name = crate_name(&output_path);
+    }
+
+    let output = match Generator::new(config).generate_from_file(input) {
+        Ok(output) => output,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    for warning in &output.warnings {
+        eprintln!("{}", warning);
+    }
+
+    let result = match args.stdout {
+        Some(StdoutFormat::Tar) => output.write_tar(io::stdout().lock()),
+        Some(StdoutFormat::Bundle) => io::stdout().lock().write_all(output.to_bundle().as_bytes()),
+        None => {
+            status("Starting repackaging into crate...");
+            if let Err(e) = output.write(&output_path) {
+                eprintln!("Error: {}", e);
+                return ExitCode::FAILURE;
+            }
+            status("Output successfully repackaged!");
+            Ok(())
+        }
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: stdout: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Run the `infer-overrides` subcommand.
+fn run_infer_overrides(input: &str, responses: &Path, output: &Path) -> ExitCode {
+    let overrides = match read_spec(input).and_then(|api| infer_overrides(&api, responses)) {
+        Ok(x) => x,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    for warning in &overrides.warnings {
+        eprintln!("{}", warning);
+    }
+
+    if let Err(e) = fs::write(output, overrides.to_toml()) {
+        eprintln!("Error: {}: {}", output.display(), e);
+        return ExitCode::FAILURE;
+    }
+    println!(
+        "Checked {} responses, found {} deviating fields. Overrides written to {}.",
+        overrides.responses,
+        overrides.fields.len(),
+        output.display()
     );
+    ExitCode::SUCCESS
+}
+
+/// Run the `types` subcommand.
+fn run_types(inputs: &[String], output: &Path, module: bool, config: GeneratorConfig) -> ExitCode {
+    let generator = Generator::new(config);
+    let result = read_json_schemas(inputs).and_then(|components| {
+        if module {
+            generator.generate_types_module(&components)
+        } else {
+            generator.generate_types(&components)
+        }
+    });
+    let types = match result {
+        Ok(x) => x,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    for warning in &types.warnings {
+        eprintln!("{}", warning);
+    }
+
+    let result = if module {
+        let module = &types.files[Path::new("types.rs")];
+        fs::write(output, module).map_err(|source| ThanixError::Io {
+            path: output.to_owned(),
+            source,
+        })
+    } else {
+        types.write(output)
+    };
+    if let Err(e) = result {
+        eprintln!("Error: {}", e);
+        return ExitCode::FAILURE;
+    }
+    println!("Types successfully written to {}.", output.display());
+    ExitCode::SUCCESS
+}
 
-    match args.input {
-        Some(file) => bindgen::generate(file, args.output, args.workaround),
-        None => println!("Error: You need to provide a YAML schema to generate from."),
+/// Name the generated crate after its output directory.
+fn crate_name(output: &Path) -> Option<String> {
+    match output.file_name() {
+        Some(name) => Some(name.to_string_lossy().into_owned()),
+        // E.g. `.` or `..`, which only have a name once resolved.
+        None => fs::canonicalize(output)
+            .ok()?
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned()),
     }
 }
@@ -0,0 +1,379 @@
+//! Read OpenAPI 3.1 documents by converting them to OpenAPI 3.0.
+//!
+//! [`openapiv3`] only models OpenAPI 3.0, whose schemas are a dialect of JSON Schema draft 4.
+//! OpenAPI 3.1 uses JSON Schema 2020-12 instead, so its schemas are rewritten to the closest 3.0
+//! equivalent before parsing:
+//!
+//! - `type: [T, "null"]` becomes `type: T` with `nullable: true`, several types become an `anyOf`.
+//! - `const: X` becomes `enum: [X]`, which is generated as a single-variant type.
+//! - `$defs` are moved to `components.schemas`.
+//! - `examples` arrays become a single `example`.
+//! - Numeric `exclusiveMinimum`/`exclusiveMaximum` become `minimum`/`maximum` with a flag.
+//! - `contentEncoding: base64` becomes `format: byte`.
+
+use std::collections::HashSet;
+
+use serde_yaml::{Mapping, Value};
+
+use crate::bundle;
+use crate::error::{ThanixError, json_pointer};
+
+/// Check whether `doc` is an OpenAPI 3.1 document.
+pub fn is_openapi_31(doc: &Value) -> bool {
+    doc.get("openapi")
+        .and_then(Value::as_str)
+        .is_some_and(|v| v.starts_with("3.1"))
+}
+
+/// Convert an OpenAPI 3.1 document to OpenAPI 3.0 in place.
+pub fn downgrade(doc: &mut Value) -> Result<(), ThanixError> {
+    let root = doc
+        .as_mapping_mut()
+        .ok_or_else(|| ThanixError::schema(&[], "document is not a mapping"))?;
+    root.insert("openapi".into(), "3.0.3".into());
+    // Only webhooks are required to be described in 3.1.
+    if !root.contains_key("paths") {
+        root.insert("paths".into(), Value::Mapping(Mapping::new()));
+    }
+
+    lift_defs(doc)?;
+    convert(doc, &mut Vec::new());
+    Ok(())
+}
+
+/// Move all `$defs` to `components.schemas`, rewriting the references to them.
+fn lift_defs(doc: &mut Value) -> Result<(), ThanixError> {
+    let mut found = Vec::new();
+    take_defs(doc, &mut Vec::new(), &mut found);
+    if found.is_empty() {
+        return Ok(());
+    }
+
+    let schemas = doc
+        .as_mapping_mut()
+        .and_then(|doc| mapping_entry(doc, "components"))
+        .and_then(|components| mapping_entry(components, "schemas"))
+        .ok_or_else(|| ThanixError::schema(&["components"], "components is not a mapping"))?;
+    let mut taken: HashSet<String> = schemas
+        .keys()
+        .filter_map(|k| k.as_str().map(str::to_owned))
+        .collect();
+
+    // Old reference prefix and the new one, e.g. `#/$defs/Tag` and `#/components/schemas/Tag`.
+    let mut renames = Vec::new();
+    let mut lifted = Vec::new();
+    for (path, defs) in found {
+        // The component schema the definitions were nested in, if any.
+        let parent = match path.as_slice() {
+            [components, schemas, name, ..]
+                if components == "components" && schemas == "schemas" =>
+            {
+                name.as_str()
+            }
+            _ => "",
+        };
+        for (key, schema) in defs {
+            let Some(key) = key.as_str() else {
+                continue;
+            };
+            let mut name = key.to_owned();
+            if taken.contains(&name) {
+                name = format!("{}{}", parent, key);
+            }
+            let mut i = 2;
+            while taken.contains(&name) {
+                name = format!("{}{}{}", parent, key, i);
+                i += 1;
+            }
+            taken.insert(name.clone());
+
+            let mut segments: Vec<&str> = path.iter().map(String::as_str).collect();
+            segments.extend(["$defs", key]);
+            renames.push((
+                json_pointer(&segments),
+                json_pointer(&["components", "schemas", &name]),
+            ));
+            lifted.push((name, schema));
+        }
+    }
+
+    for (name, schema) in lifted {
+        schemas.insert(name.into(), schema);
+    }
+    rename_refs(doc, &renames);
+    Ok(())
+}
+
+/// Remove all `$defs` from `value` at `path`, collecting them with the path they were found at.
+fn take_defs(value: &mut Value, path: &mut Vec<String>, found: &mut Vec<(Vec<String>, Mapping)>) {
+    match value {
+        Value::Mapping(map) => {
+            if let Some(Value::Mapping(mut defs)) = map.remove("$defs") {
+                // Definitions can have definitions of their own.
+                for (key, v) in defs.iter_mut() {
+                    path.extend([
+                        "$defs".to_owned(),
+                        key.as_str().unwrap_or_default().to_owned(),
+                    ]);
+                    take_defs(v, path, found);
+                    path.truncate(path.len() - 2);
+                }
+                found.push((path.clone(), defs));
+            }
+            for (key, v) in map.iter_mut() {
+                path.push(key.as_str().unwrap_or_default().to_owned());
+                take_defs(v, path, found);
+                path.pop();
+            }
+        }
+        Value::Sequence(items) => {
+            for (i, v) in items.iter_mut().enumerate() {
+                path.push(i.to_string());
+                take_defs(v, path, found);
+                path.pop();
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Rewrite all references starting with one of the old prefixes in `renames`.
+fn rename_refs(value: &mut Value, renames: &[(String, String)]) {
+    match value {
+        Value::Mapping(map) => {
+            if let Some(Value::String(reference)) = map.get_mut("$ref") {
+                for (old, new) in renames {
+                    if let Some(rest) = reference.strip_prefix(old.as_str())
+                        && (rest.is_empty() || rest.starts_with('/'))
+                    {
+                        *reference = format!("{}{}", new, rest);
+                        break;
+                    }
+                }
+            }
+            for (_, v) in map.iter_mut() {
+                rename_refs(v, renames);
+            }
+        }
+        Value::Sequence(items) => {
+            for v in items {
+                rename_refs(v, renames);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Rewrite the JSON Schema 2020-12 keywords of all schemas within `value`, found at `path`.
+fn convert(value: &mut Value, path: &mut Vec<String>) {
+    match value {
+        Value::Mapping(map) => {
+            // Only schemas, as e.g. a property named `const` must stay as it is.
+            if bundle::is_schema_position(path) {
+                convert_schema(map);
+            }
+            for (key, v) in map.iter_mut() {
+                path.push(key.as_str().unwrap_or_default().to_owned());
+                convert(v, path);
+                path.pop();
+            }
+        }
+        Value::Sequence(items) => {
+            for (i, v) in items.iter_mut().enumerate() {
+                path.push(i.to_string());
+                convert(v, path);
+                path.pop();
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Rewrite the JSON Schema 2020-12 keywords of a single schema.
+fn convert_schema(map: &mut Mapping) {
+    if let Some(Value::Sequence(types)) = map.get("type") {
+        let nullable = types.iter().any(|t| t.as_str() == Some("null"));
+        let types: Vec<Value> = types
+            .iter()
+            .filter(|t| t.as_str() != Some("null"))
+            .cloned()
+            .collect();
+        match types.as_slice() {
+            [] => {
+                map.remove("type");
+            }
+            [typ] => {
+                map.insert("type".into(), typ.clone());
+            }
+            _ => {
+                map.remove("type");
+                let members = types
+                    .into_iter()
+                    .map(|t| {
+                        let mut member = Mapping::new();
+                        member.insert("type".into(), t);
+                        Value::Mapping(member)
+                    })
+                    .collect();
+                map.insert("anyOf".into(), Value::Sequence(members));
+            }
+        }
+        if nullable {
+            map.insert("nullable".into(), true.into());
+        }
+    } else if map.get("type").and_then(Value::as_str) == Some("null") {
+        map.remove("type");
+        map.insert("nullable".into(), true.into());
+    }
+
+    // `oneOf: [{$ref: X}, {type: null}]` is how 3.1 writes a nullable reference.
+    for key in ["oneOf", "anyOf"] {
+        if let Some(Value::Sequence(members)) = map.get_mut(key)
+            && members.len() > 1
+        {
+            let before = members.len();
+            members.retain(|m| m.get("type").and_then(Value::as_str) != Some("null"));
+            if members.len() < before {
+                if members.len() == 1 {
+                    let members = map.remove(key).unwrap_or_default();
+                    map.insert("allOf".into(), members);
+                }
+                map.insert("nullable".into(), true.into());
+            }
+        }
+    }
+
+    if let Some(value) = map.remove("const") {
+        if !map.contains_key("type") {
+            let typ = match &value {
+                Value::String(_) => Some("string"),
+                Value::Bool(_) => Some("boolean"),
+                Value::Number(n) if n.is_f64() => Some("number"),
+                Value::Number(_) => Some("integer"),
+                _ => None,
+            };
+            if let Some(typ) = typ {
+                map.insert("type".into(), typ.into());
+            }
+        }
+        map.insert("enum".into(), Value::Sequence(vec![value]));
+    }
+
+    if let Some(Value::Sequence(examples)) = map.get("examples") {
+        let example = examples.first().cloned();
+        map.remove("examples");
+        if let Some(example) = example
+            && !map.contains_key("example")
+        {
+            map.insert("example".into(), example);
+        }
+    }
+
+    for (exclusive, bound) in [
+        ("exclusiveMinimum", "minimum"),
+        ("exclusiveMaximum", "maximum"),
+    ] {
+        if let Some(Value::Number(n)) = map.get(exclusive) {
+            let n = n.clone();
+            map.insert(bound.into(), Value::Number(n));
+            map.insert(exclusive.into(), true.into());
+        }
+    }
+
+    if map.get("contentEncoding").and_then(Value::as_str) == Some("base64")
+        && !map.contains_key("format")
+    {
+        map.remove("contentEncoding");
+        map.insert("format".into(), "byte".into());
+    }
+}
+
+/// Get the mapping at `key` of `map`, inserting an empty one if there is none.
+fn mapping_entry<'a>(map: &'a mut Mapping, key: &str) -> Option<&'a mut Mapping> {
+    map.entry(key.into())
+        .or_insert_with(|| Value::Mapping(Mapping::new()))
+        .as_mapping_mut()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn downgraded(yaml: &str) -> Value {
+        let mut doc: Value = serde_yaml::from_str(yaml).unwrap();
+        downgrade(&mut doc).unwrap();
+        doc
+    }
+
+    #[test]
+    fn test_downgrade_types() {
+        let doc = downgraded(
+            "openapi: 3.1.0\n\
+             components:\n\
+             \x20 schemas:\n\
+             \x20   A: {type: [string, 'null']}\n\
+             \x20   B: {type: [string, integer]}\n\
+             \x20   C: {oneOf: [{$ref: '#/components/schemas/A'}, {type: 'null'}]}\n\
+             \x20   D: {const: dcim.device}\n\
+             \x20   E: {type: integer, exclusiveMinimum: 0, examples: [1, 2]}\n\
+             \x20   F: {type: object, properties: {const: {type: string}}}\n",
+        );
+        assert_eq!(doc["openapi"], "3.0.3");
+        assert!(doc["paths"].is_mapping());
+        let schemas = &doc["components"]["schemas"];
+        assert_eq!(schemas["A"]["type"], "string");
+        assert_eq!(schemas["A"]["nullable"], true);
+        assert_eq!(schemas["B"]["anyOf"][1]["type"], "integer");
+        assert_eq!(schemas["C"]["allOf"][0]["$ref"], "#/components/schemas/A");
+        assert_eq!(schemas["C"]["nullable"], true);
+        assert_eq!(schemas["D"]["type"], "string");
+        assert_eq!(schemas["D"]["enum"][0], "dcim.device");
+        assert_eq!(schemas["E"]["minimum"], 0);
+        assert_eq!(schemas["E"]["exclusiveMinimum"], true);
+        assert_eq!(schemas["E"]["example"], 1);
+        assert_eq!(schemas["F"]["properties"]["const"]["type"], "string");
+    }
+
+    #[test]
+    fn test_downgrade_defs() {
+        let doc = downgraded(
+            "openapi: 3.1.0\n\
+             $defs:\n\
+             \x20 Tag: {type: string}\n\
+             components:\n\
+             \x20 schemas:\n\
+             \x20   Tag: {type: integer}\n\
+             \x20   Device:\n\
+             \x20     type: object\n\
+             \x20     properties:\n\
+             \x20       tag: {$ref: '#/$defs/Tag'}\n\
+             \x20       site: {$ref: '#/components/schemas/Device/$defs/Site'}\n\
+             \x20     $defs:\n\
+             \x20       Site: {type: object}\n",
+        );
+        let schemas = &doc["components"]["schemas"];
+        let device = &schemas["Device"];
+        assert!(device.get("$defs").is_none());
+        assert_eq!(
+            device["properties"]["site"]["$ref"],
+            "#/components/schemas/Site"
+        );
+        assert_eq!(schemas["Site"]["type"], "object");
+        // `Tag` is already taken.
+        assert_eq!(
+            device["properties"]["tag"]["$ref"],
+            "#/components/schemas/Tag2"
+        );
+        assert_eq!(schemas["Tag2"]["type"], "string");
+    }
+
+    #[test]
+    fn test_is_openapi_31() {
+        assert!(is_openapi_31(
+            &serde_yaml::from_str("openapi: 3.1.0").unwrap()
+        ));
+        assert!(!is_openapi_31(
+            &serde_yaml::from_str("openapi: 3.0.3").unwrap()
+        ));
+    }
+}
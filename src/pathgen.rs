@@ -1,126 +1,293 @@
 //! Generate API request functions.
 
-use crate::bindgen::{self, make_comment};
+use std::collections::HashSet;
+
+use crate::auth;
+use crate::bindgen::{self, TypeContext, make_comment};
+use crate::error::ThanixError;
 use check_keyword::CheckKeyword;
 use convert_case::{Case, Casing};
-use openapiv3::{Operation, Parameter, ParameterSchemaOrContent, PathItem, ReferenceOr};
-
-pub fn generate(name: &str, path_item: &PathItem) -> Option<String> {
+use openapiv3::{
+    MediaType, Operation, Parameter, ParameterSchemaOrContent, PathItem, QueryStyle, ReferenceOr,
+    Schema,
+};
+
+/// Media types of request bodies which can be sent, with the method of the request builder sending
+/// them, in order of preference.
+const BODY_MEDIA_TYPES: &[(&str, &str)] = &[
+    ("application/json", "json"),
+    ("application/x-www-form-urlencoded", "form"),
+];
+
+/// Generate the API request functions for every operation of a path.
+///
+/// # Parameters
+///
+/// * `name: &str` - The path the operations are found at.
+/// * `path_item: &PathItem` - The operations of this path.
+/// * `async_mode: bool` - Whether to emit `async fn`s over `reqwest::Client` instead of blocking
+///   functions over `reqwest::blocking::Client`.
+/// * `ctx: &mut TypeContext` - Receives the inline types hoisted out of parameters and bodies.
+/// * `signatures: &mut Vec<Signature>` - Receives the signatures of the generated functions, see
+///   [`gen_client`].
+///
+/// # Returns
+///
+/// * `Result<String, ThanixError>` - The string representation of all functions of this path, or
+///   an error pointing to an operation we can't generate a function for.
+pub fn generate(
+    name: &str,
+    path_item: &PathItem,
+    async_mode: bool,
+    ctx: &mut TypeContext,
+    signatures: &mut Vec<Signature>,
+) -> Result<String, ThanixError> {
     let mut result = String::new();
 
     if let Some(op) = &path_item.get {
-        result += gen_fn(name, "get", op).as_str();
+        result += gen_fn(name, "get", op, async_mode, ctx, signatures)?.as_str();
     }
     if let Some(op) = &path_item.put {
-        result += gen_fn(name, "put", op).as_str();
+        result += gen_fn(name, "put", op, async_mode, ctx, signatures)?.as_str();
     }
     if let Some(op) = &path_item.post {
-        result += gen_fn(name, "post", op).as_str();
+        result += gen_fn(name, "post", op, async_mode, ctx, signatures)?.as_str();
     }
     if let Some(op) = &path_item.delete {
-        result += gen_fn(name, "delete", op).as_str();
+        result += gen_fn(name, "delete", op, async_mode, ctx, signatures)?.as_str();
     }
     if let Some(op) = &path_item.options {
-        result += gen_fn(name, "options", op).as_str();
+        result += gen_fn(name, "options", op, async_mode, ctx, signatures)?.as_str();
     }
     if let Some(op) = &path_item.head {
-        result += gen_fn(name, "head", op).as_str();
+        result += gen_fn(name, "head", op, async_mode, ctx, signatures)?.as_str();
     }
     if let Some(op) = &path_item.patch {
-        result += gen_fn(name, "patch", op).as_str();
+        result += gen_fn(name, "patch", op, async_mode, ctx, signatures)?.as_str();
     }
     if let Some(op) = &path_item.trace {
-        result += gen_fn(name, "trace", op).as_str();
+        result += gen_fn(name, "trace", op, async_mode, ctx, signatures)?.as_str();
     }
 
-    Some(result)
+    Ok(result)
 }
 
-fn gen_fn(name: &str, op_type: &str, op: &Operation) -> String {
+fn gen_fn(
+    name: &str,
+    op_type: &str,
+    op: &Operation,
+    async_mode: bool,
+    ctx: &mut TypeContext,
+    signatures: &mut Vec<Signature>,
+) -> Result<String, ThanixError> {
     let mut result = String::new();
+    // Futures returned by the async client need to be awaited.
+    let await_suffix = if async_mode { ".await" } else { "" };
 
     // Build function name.
     let fn_name = op
         .operation_id
         .clone()
         .unwrap_or(make_fn_name_from_path(name) + "_" + op_type);
+    let fn_type_name = fn_name.to_case(Case::Pascal);
+    let fn_query_name = fn_type_name.clone() + "Query";
+    let fn_response_name = fn_type_name.clone() + "Response";
+    // Hoisted types must not be shadowed by the types generated in the paths module.
+    ctx.reserve(&fn_query_name);
+    ctx.reserve(&fn_response_name);
 
     let mut fn_query_params = Vec::new();
     let mut fn_header_params = Vec::new();
     let mut fn_path_params = Vec::new();
+    let mut fn_cookie_params = Vec::new();
 
     // Assign all parameters to their respective slots.
-    for param in op.parameters.clone() {
+    for (index, param) in op.parameters.iter().enumerate() {
         // Filter out only parameter items.
         let p = match param {
             ReferenceOr::Item(x) => x,
-            _ => continue,
+            ReferenceOr::Reference { reference } => {
+                return Err(unresolved(
+                    reference,
+                    &[name, op_type, "parameters", &index.to_string()],
+                ));
+            }
+        };
+        let parameter_data = p.parameter_data_ref();
+        // We only respect Schemas.
+        let param_type = match &parameter_data.format {
+            ParameterSchemaOrContent::Schema(schema) => schema,
+            ParameterSchemaOrContent::Content(_) => {
+                // The async variant of the operation has the same parameters.
+                if !async_mode {
+                    ctx.warnings.push(format!(
+                        "Parameter {} of {} is described by `content` and was skipped, only `schema` is supported",
+                        parameter_data.name, fn_name
+                    ));
+                }
+                continue;
+            }
         };
         match p {
             // If we have a query, append as a field to the query struct.
-            Parameter::Query { parameter_data, .. } => {
-                // We only respect Schemas.
-                let query_param_type = match &parameter_data.format {
-                    ParameterSchemaOrContent::Schema(schema) => schema,
-                    _ => continue,
+            Parameter::Query { style, .. } => {
+                // Arrays are always sent as repeated `name=value` pairs.
+                let delimited = match style {
+                    QueryStyle::SpaceDelimited => Some("space"),
+                    QueryStyle::PipeDelimited => Some("pipe"),
+                    _ => parameter_data
+                        .extensions
+                        .get("x-collectionFormat")
+                        .and_then(|x| x.as_str())
+                        .filter(|x| *x == "tsv")
+                        .map(|_| "tab"),
                 };
+                if let Some(delimiter) = delimited
+                    && !async_mode
+                {
+                    ctx.warnings.push(format!(
+                        "Query parameter {} of {} is sent as repeated values instead of {}-delimited ones",
+                        parameter_data.name, fn_name, delimiter
+                    ));
+                }
                 // Format as a struct field.
                 fn_query_params.push(format!(
                     "{}\tpub {}: Option<{}>,\n",
-                    make_comment(parameter_data.description, 1),
-                    parameter_data.name.into_safe(),
-                    bindgen::type_to_string(query_param_type)
+                    make_comment(parameter_data.description.clone(), 1),
+                    parameter_data.name.clone().into_safe(),
+                    bindgen::type_to_string(
+                        param_type,
+                        &format!(
+                            "{}{}",
+                            fn_query_name,
+                            parameter_data.name.to_case(Case::Pascal)
+                        ),
+                        ctx
+                    )
                 ))
             }
             // If we have a header, append to the header params.
-            Parameter::Header { parameter_data, .. } => {
-                // We only respect Schemas.
-                let header_param_type = match &parameter_data.format {
-                    ParameterSchemaOrContent::Schema(schema) => schema,
-                    _ => continue,
-                };
-
+            Parameter::Header { .. } => {
                 fn_header_params.push((
                     parameter_data.name.clone(),
-                    bindgen::type_to_string(header_param_type),
+                    bindgen::type_to_string(
+                        param_type,
+                        &format!(
+                            "{}{}",
+                            fn_type_name,
+                            parameter_data.name.to_case(Case::Pascal)
+                        ),
+                        ctx,
+                    ),
                 ));
             }
             // If we have a path, append to the path params.
-            Parameter::Path { parameter_data, .. } => {
-                // We only respect Schemas.
-                let path_param_type = match &parameter_data.format {
-                    ParameterSchemaOrContent::Schema(schema) => schema,
-                    _ => continue,
-                };
-
-                fn_path_params.push(format!(
-                    ", {}: {}",
-                    parameter_data.name.into_safe(),
-                    bindgen::type_to_string(path_param_type)
+            Parameter::Path { .. } => {
+                fn_path_params.push((
+                    parameter_data.name.clone().into_safe(),
+                    bindgen::type_to_string(
+                        param_type,
+                        &format!(
+                            "{}{}",
+                            fn_type_name,
+                            parameter_data.name.to_case(Case::Pascal)
+                        ),
+                        ctx,
+                    ),
                 ));
             }
-            // TODO
+            // Cookies are all sent in a single header.
             Parameter::Cookie { .. } => {
-                todo!()
+                fn_cookie_params.push((
+                    parameter_data.name.clone(),
+                    bindgen::type_to_string(
+                        param_type,
+                        &format!(
+                            "{}{}",
+                            fn_type_name,
+                            parameter_data.name.to_case(Case::Pascal)
+                        ),
+                        ctx,
+                    ),
+                ));
             }
         }
     }
 
-    // Build the request body.
-    let fn_request_type = match &op.request_body {
-        Some(req) => match req {
-            ReferenceOr::Item(x) => match x.content.get("application/json") {
-                Some(media) => Some(bindgen::type_to_string(&media.schema.clone().unwrap())),
-                None => None,
-            },
-            _ => None,
-        },
+    // Build the request body, sent as JSON or as a form.
+    let fn_request = match &op.request_body {
+        Some(ReferenceOr::Item(x)) => {
+            let body = BODY_MEDIA_TYPES.iter().find_map(|(media_type, method)| {
+                x.content
+                    .get(*media_type)
+                    .map(|media| (*media_type, media, *method))
+            });
+            match body {
+                Some((media_type, media, method)) => Some((
+                    bindgen::type_to_string(
+                        media_schema(
+                            media,
+                            &[name, op_type, "requestBody", "content", media_type],
+                        )?,
+                        &format!("{}Request", fn_type_name),
+                        ctx,
+                    ),
+                    method,
+                )),
+                None => {
+                    // The async variant of the operation has the same body.
+                    if !async_mode && !x.content.is_empty() {
+                        ctx.warnings.push(format!(
+                            "The body of {} isn't sent, as none of its media types ({}) is supported",
+                            fn_name,
+                            x.content.keys().cloned().collect::<Vec<_>>().join(", ")
+                        ));
+                    }
+                    None
+                }
+            }
+        }
+        Some(ReferenceOr::Reference { reference }) => {
+            return Err(unresolved(reference, &[name, op_type, "requestBody"]));
+        }
         None => None,
     };
 
+    // Build the response types.
+    let mut fn_responses = Vec::new();
+    for (status, response) in &op.responses.responses {
+        let x = match response {
+            ReferenceOr::Item(x) => x,
+            ReferenceOr::Reference { reference } => {
+                return Err(unresolved(
+                    reference,
+                    &[name, op_type, "responses", &status.to_string()],
+                ));
+            }
+        };
+        let typ = match x.content.get("application/json") {
+            Some(media) => Some(bindgen::type_to_string(
+                media_schema(
+                    media,
+                    &[
+                        name,
+                        op_type,
+                        "responses",
+                        &status.to_string(),
+                        "content",
+                        "application/json",
+                    ],
+                )?,
+                &format!("{}{}", fn_response_name, status),
+                ctx,
+            )),
+            None => None,
+        };
+        fn_responses.push((status, typ));
+    }
+
     // Build the query struct for this function if we have at least one parameter.
-    let need_query = fn_query_params.len() > 0;
-    let fn_query_name = fn_name.to_case(Case::Pascal) + "Query";
+    let need_query = !fn_query_params.is_empty();
     let fn_query_struct = format!(
         "#[derive(Serialize, Deserialize, Debug, Default, Clone)]\npub struct {} {{\n{}\n}}\n",
         fn_query_name,
@@ -132,25 +299,18 @@ fn gen_fn(name: &str, op_type: &str, op: &Operation) -> String {
     }
 
     // Build the response enum.
-    let fn_response_name = fn_name.to_case(Case::Pascal) + "Response";
     result += "#[derive(Debug)]\npub enum ";
     result += &fn_response_name;
     result += " {\n";
 
-    for (status, response) in &op.responses.responses {
-        result += "\t";
-        match response {
-            ReferenceOr::Item(x) => {
-                result += &format!("Http{}", status);
-                if let Some(y) = &x.content.get("application/json") {
-                    result += "(";
-                    result += &bindgen::type_to_string(&y.schema.as_ref().unwrap());
-                    result += ")";
-                }
-                result += ",\n";
-            }
-            _ => (),
+    for (status, typ) in &fn_responses {
+        result += &format!("\tHttp{}", status);
+        if let Some(typ) = typ {
+            result += "(";
+            result += typ;
+            result += ")";
         }
+        result += ",\n";
     }
 
     result += "\tOther(Response)\n";
@@ -160,31 +320,46 @@ fn gen_fn(name: &str, op_type: &str, op: &Operation) -> String {
     result += &bindgen::make_comment(op.description.clone(), 0);
 
     // Build function declaration.
-    result += "pub fn ";
+    result += if async_mode {
+        "pub async fn "
+    } else {
+        "pub fn "
+    };
     result += &fn_name;
     result += "(state: &ThanixClient";
 
+    let mut args = Vec::new();
     // Build the query arg.
     if need_query {
-        result += ", query: ";
-        result += &fn_query_name;
+        args.push(("query".to_owned(), fn_query_name.clone()));
     }
 
-    // Build the JSON arg.
-    if let Some(x) = &fn_request_type {
-        result += ", body: ";
-        result += x;
+    // Build the body arg.
+    if let Some((x, _)) = &fn_request {
+        args.push(("body".to_owned(), x.clone()));
     }
 
     // Build the path args.
-    result += &fn_path_params.into_iter().collect::<String>();
+    args.extend(fn_path_params);
 
     // Build the header args.
-    result += &fn_header_params
+    args.extend(
+        fn_header_params
+            .iter()
+            .map(|(name, ty)| (format!("header_{}", name.to_case(Case::Snake)), ty.clone())),
+    );
+
+    // Build the cookie args.
+    args.extend(
+        fn_cookie_params
+            .iter()
+            .map(|(name, ty)| (format!("cookie_{}", name.to_case(Case::Snake)), ty.clone())),
+    );
+
+    result += &args
         .iter()
-        .map(|(name, ty)| format!(", header_{}: {}", name, ty))
+        .map(|(name, ty)| format!(", {}: {}", name, ty))
         .collect::<String>();
-
     result += ") -> ";
 
     // Build the response type.
@@ -210,24 +385,49 @@ fn gen_fn(name: &str, op_type: &str, op: &Operation) -> String {
     if need_query {
         result += ", qstring_clean";
     }
-    result += "))\n";
-
-    // Auth header.
-    result +=
-        "\t\t.header(\"Authorization\", format!(\"Token {}\", state.authentication_token));\n";
+    result += "));\n";
+
+    // Credentials, sent in the same `Cookie` header as the cookie parameters.
+    let cookie = if fn_cookie_params.is_empty() {
+        "None".to_owned()
+    } else {
+        let (pairs, args): (Vec<_>, Vec<_>) = fn_cookie_params
+            .iter()
+            .map(|(name, _)| {
+                (
+                    format!("{}={{}}", name),
+                    format!("cookie_{}", name.to_case(Case::Snake)),
+                )
+            })
+            .unzip();
+        format!(
+            "Some(format!(\"{}\", {}))",
+            pairs.join("; "),
+            args.join(", ")
+        )
+    };
+    result += &format!(
+        "\tr#request = state.authenticate(r#request, {}, {});\n",
+        auth::security_arg(op),
+        cookie
+    );
 
-    // JSON body.
-    if let Some(_) = &fn_request_type {
-        result += "\tr#request = r#request.json(&body);\n";
+    // JSON or form body.
+    if let Some((_, method)) = &fn_request {
+        result += &format!("\tr#request = r#request.{}(&body);\n", method);
     }
-    fn_header_params
-        .iter()
-        .for_each(|(name, _)| result += &format!("\n.header(\"{}\", header_{})", &name, &name));
+    fn_header_params.iter().for_each(|(name, _)| {
+        result += &format!(
+            "\tr#request = r#request.header(\"{}\", header_{}.to_string());\n",
+            &name,
+            name.to_case(Case::Snake)
+        )
+    });
 
     result += "\t#[cfg(feature = \"debug_messages\")]\n";
     result += "\teprint!(\"{:?} = \", &r#request);\n";
 
-    result += "\tlet r#response = r#request.send()?;\n";
+    result += &format!("\tlet r#response = r#request.send(){}?;\n", await_suffix);
 
     result += "\t#[cfg(feature = \"debug_messages\")]\n";
     result += "\teprintln!(\"= {:?}\", &r#response);\n";
@@ -235,20 +435,12 @@ fn gen_fn(name: &str, op_type: &str, op: &Operation) -> String {
     result += "\tmatch r#response.status().as_u16() {\n";
 
     // Match response code.
-    for (status, response) in &op.responses.responses {
-        match response {
-            ReferenceOr::Item(x) => {
-                if let Some(y) = &x.content.get("application/json") {
-                    result += &format!(
-                        "\t\t{} => {{ Ok({}::Http{}(r#response.json::<{}>()?)) }},\n",
-                        status,
-                        &fn_response_name,
-                        status,
-                        &bindgen::type_to_string(&y.schema.as_ref().unwrap())
-                    );
-                }
-            }
-            _ => (),
+    for (status, typ) in &fn_responses {
+        if let Some(typ) = typ {
+            result += &format!(
+                "\t\t{} => {{ Ok({}::Http{}(r#response.json::<{}>(){}?)) }},\n",
+                status, &fn_response_name, status, typ, await_suffix
+            );
         }
     }
 
@@ -257,10 +449,154 @@ fn gen_fn(name: &str, op_type: &str, op: &Operation) -> String {
     result += &fn_response_name;
     result += "::Other(r#response)) }\n\t}\n}\n";
 
-    return result;
+    signatures.push(Signature {
+        name: fn_name,
+        description: op.description.clone(),
+        args,
+        response: fn_response_name,
+    });
+    Ok(result)
 }
 
-fn make_fn_name_from_path(input: &str) -> String {
+/// The signature of a generated API function, to call it from a method of the client.
+pub struct Signature {
+    /// The name of the function, e.g. `dcim_devices_list`.
+    pub name: String,
+    /// The description of the operation.
+    pub description: Option<String>,
+    /// The names and types of all arguments after the client.
+    pub args: Vec<(String, String)>,
+    /// The name of the response enum.
+    pub response: String,
+}
+
+/// Get the name of the struct grouping the methods of a module, e.g. `DcimApi`.
+pub fn client_name(module: &str) -> String {
+    format!("{}Api", module.to_case(Case::Pascal))
+}
+
+/// Methods of the client itself, which the methods returning the operations of a module must not
+/// shadow.
+const CLIENT_METHODS: &[&str] = &[
+    "builder",
+    "new",
+    "auth",
+    "set_auth",
+    "authenticate",
+    "from_builder",
+];
+
+/// Get the name of the method of the client returning the operations of a module, e.g. `dcim`, or
+/// `auth_api` for a module which would clash with a method of the client.
+pub fn accessor_name(module: &str) -> String {
+    if CLIENT_METHODS.contains(&module) {
+        format!("{}_api", module)
+    } else {
+        module.to_owned()
+    }
+}
+
+/// Generate a struct with a method for every API function of a module, and a method of the
+/// client returning it, e.g. `client.dcim().devices_list(query)`.
+///
+/// The methods just call the free functions, which stay available.
+///
+/// # Parameters
+///
+/// * `module: &str` - The name of the module, which becomes the name of the client method.
+/// * `signatures: &[Signature]` - The functions of the module.
+/// * `async_mode: bool` - Whether the functions are `async`.
+///
+/// # Returns
+///
+/// * `String` - The struct and both `impl` blocks.
+pub fn gen_client(module: &str, signatures: &[Signature], async_mode: bool) -> String {
+    let client_name = client_name(module);
+    let mut result = format!(
+        "/// The operations of the `{0}` module, see [`ThanixClient::{2}`].\n\
+         #[derive(Clone, Copy)]\n\
+         pub struct {1}<'a> {{\n\
+         \tclient: &'a ThanixClient,\n\
+         }}\n\n\
+         impl ThanixClient {{\n\
+         \t/// Get the operations of the `{0}` module.\n\
+         \tpub fn {2}(&self) -> {1}<'_> {{\n\
+         \t\t{1} {{ client: self }}\n\
+         \t}}\n\
+         }}\n\n\
+         impl<'a> {1}<'a> {{\n",
+        module,
+        client_name,
+        accessor_name(module)
+    );
+
+    // The module is left out of the method names if they start with it.
+    let prefix = format!("{}_", module.trim_start_matches('_'));
+    let mut names = HashSet::new();
+    for signature in signatures {
+        let short = signature
+            .name
+            .strip_prefix(&prefix)
+            .filter(|short| {
+                short.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+                    && !short.is_keyword()
+                    && !signatures.iter().any(|s| s.name == *short)
+            })
+            .unwrap_or(&signature.name);
+        let method = if names.insert(short) {
+            short
+        } else {
+            &signature.name
+        };
+
+        result += &make_comment(signature.description.clone(), 1);
+        result += &format!(
+            "\tpub {}fn {}(&self{}) -> Result<{}, Error> {{\n\t\t{}(self.client{}){}\n\t}}\n",
+            if async_mode { "async " } else { "" },
+            method,
+            signature
+                .args
+                .iter()
+                .map(|(name, ty)| format!(", {}: {}", name, ty))
+                .collect::<String>(),
+            signature.response,
+            signature.name,
+            signature
+                .args
+                .iter()
+                .map(|(name, _)| format!(", {}", name))
+                .collect::<String>(),
+            if async_mode { ".await" } else { "" },
+        );
+    }
+    result += "}\n";
+    result
+}
+
+/// Get the schema of a media type of the operation at `location` below `#/paths`.
+fn media_schema<'a>(
+    media: &'a MediaType,
+    location: &[&str],
+) -> Result<&'a ReferenceOr<Schema>, ThanixError> {
+    media.schema.as_ref().ok_or_else(|| {
+        let mut segments = vec!["paths"];
+        segments.extend_from_slice(location);
+        ThanixError::schema(&segments, "media type has no schema")
+    })
+}
+
+/// Error for a `$ref` at `location` below `#/paths` which hasn't been resolved, e.g. because it
+/// points into another file.
+pub fn unresolved(reference: &str, location: &[&str]) -> ThanixError {
+    let mut segments = vec!["paths"];
+    segments.extend_from_slice(location);
+    ThanixError::schema(
+        &segments,
+        format!("`$ref` to `{}` couldn't be resolved", reference),
+    )
+}
+
+pub fn make_fn_name_from_path(input: &str) -> String {
     input.replace("/api/", "").replace('/', "_")
 }
 
@@ -273,18 +609,32 @@ mod tests {
     #[test]
     fn test_generate_no_op() {
         let path_item = PathItem::default();
-        let result = generate("/test", &path_item);
-        assert_eq!(result, Some(String::new()));
+        let result = generate(
+            "/test",
+            &path_item,
+            false,
+            &mut TypeContext::default(),
+            &mut Vec::new(),
+        );
+        assert_eq!(result.unwrap(), String::new());
     }
 
     #[test]
     fn test_generate_multi_op() {
-        let mut path_item = PathItem::default();
-        path_item.get = Some(Operation::default());
-        path_item.post = Some(Operation::default());
+        let path_item = PathItem {
+            get: Some(Operation::default()),
+            post: Some(Operation::default()),
+            ..Default::default()
+        };
 
-        let result = generate("/test", &path_item);
-        assert!(result.is_some());
+        let result = generate(
+            "/test",
+            &path_item,
+            false,
+            &mut TypeContext::default(),
+            &mut Vec::new(),
+        );
+        assert!(result.is_ok());
         let output = result.unwrap();
         assert!(output.contains("get"));
         assert!(output.contains("post"));
@@ -292,18 +642,26 @@ mod tests {
 
     #[test]
     fn test_generate_all_op() {
-        let mut path_item = PathItem::default();
-        path_item.get = Some(Operation::default());
-        path_item.put = Some(Operation::default());
-        path_item.post = Some(Operation::default());
-        path_item.delete = Some(Operation::default());
-        path_item.options = Some(Operation::default());
-        path_item.head = Some(Operation::default());
-        path_item.patch = Some(Operation::default());
-        path_item.trace = Some(Operation::default());
-
-        let result = generate("/test", &path_item);
-        assert!(result.is_some());
+        let path_item = PathItem {
+            get: Some(Operation::default()),
+            put: Some(Operation::default()),
+            post: Some(Operation::default()),
+            delete: Some(Operation::default()),
+            options: Some(Operation::default()),
+            head: Some(Operation::default()),
+            patch: Some(Operation::default()),
+            trace: Some(Operation::default()),
+            ..Default::default()
+        };
+
+        let result = generate(
+            "/test",
+            &path_item,
+            false,
+            &mut TypeContext::default(),
+            &mut Vec::new(),
+        );
+        assert!(result.is_ok());
         let output = result.unwrap();
         assert!(output.contains("get"));
         assert!(output.contains("put"));
@@ -318,9 +676,232 @@ mod tests {
     #[test]
     fn test_gen_fn_basic() {
         let operation = Operation::default();
-        let result = gen_fn("/test", "get", &operation);
+        let result = gen_fn(
+            "/test",
+            "get",
+            &operation,
+            false,
+            &mut TypeContext::default(),
+            &mut Vec::new(),
+        )
+        .unwrap();
         assert!(result.contains("pub fn"));
         assert!(result.contains("get"));
+        // Without any declared requirements, the credentials are always sent.
+        assert!(result.contains("\tr#request = state.authenticate(r#request, None, None);\n"));
+    }
+
+    #[test]
+    fn test_gen_fn_async() {
+        let operation = Operation::default();
+        let result = gen_fn(
+            "/test",
+            "get",
+            &operation,
+            true,
+            &mut TypeContext::default(),
+            &mut Vec::new(),
+        )
+        .unwrap();
+        assert!(result.contains("pub async fn"));
+        assert!(result.contains("r#request.send().await?"));
+    }
+
+    #[test]
+    fn test_gen_fn_cookie() {
+        let operation: Operation = serde_yaml::from_str(
+            "parameters:\n\
+             \x20 - {name: sessionid, in: cookie, schema: {type: string}}\n\
+             \x20 - {name: csrftoken, in: cookie, schema: {type: string}}\n\
+             responses: {}\n",
+        )
+        .unwrap();
+        let result = gen_fn(
+            "/test",
+            "get",
+            &operation,
+            false,
+            &mut TypeContext::default(),
+            &mut Vec::new(),
+        )
+        .unwrap();
+        assert!(result.contains(", cookie_sessionid: String, cookie_csrftoken: String)"));
+        assert!(result.contains(
+            "state.authenticate(r#request, None, Some(format!(\"sessionid={}; csrftoken={}\", cookie_sessionid, cookie_csrftoken)));"
+        ));
+        assert!(!result.contains("\"Cookie\""));
+    }
+
+    #[test]
+    fn test_gen_fn_body() {
+        let operation: Operation = serde_yaml::from_str(
+            "requestBody:\n\
+             \x20 content:\n\
+             \x20   application/x-www-form-urlencoded: {schema: {$ref: '#/components/schemas/Login'}}\n\
+             \x20   multipart/form-data: {schema: {$ref: '#/components/schemas/Login'}}\n\
+             responses: {}\n",
+        )
+        .unwrap();
+        let mut ctx = TypeContext::default();
+        let result = gen_fn(
+            "/login",
+            "post",
+            &operation,
+            false,
+            &mut ctx,
+            &mut Vec::new(),
+        )
+        .unwrap();
+        assert!(result.contains(", body: Login)"));
+        assert!(result.contains("\tr#request = r#request.form(&body);\n"));
+        assert!(ctx.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_gen_fn_unsupported_body() {
+        let operation: Operation = serde_yaml::from_str(
+            "operationId: upload\n\
+             requestBody:\n\
+             \x20 content:\n\
+             \x20   multipart/form-data: {schema: {type: object}}\n\
+             \x20   application/octet-stream: {}\n\
+             responses: {}\n",
+        )
+        .unwrap();
+        let mut ctx = TypeContext::default();
+        let result = gen_fn(
+            "/upload",
+            "post",
+            &operation,
+            false,
+            &mut ctx,
+            &mut Vec::new(),
+        )
+        .unwrap();
+        assert!(!result.contains("body"));
+        assert_eq!(
+            ctx.warnings,
+            vec![
+                "The body of upload isn't sent, as none of its media types (multipart/form-data, application/octet-stream) is supported"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_gen_fn_delimited_query() {
+        let operation: Operation = serde_yaml::from_str(
+            "operationId: search\n\
+             parameters:\n\
+             \x20 - {name: id, in: query, style: pipeDelimited, schema: {type: array, items: {type: integer}}}\n\
+             \x20 - {name: q, in: query, x-collectionFormat: tsv, schema: {type: array, items: {type: string}}}\n\
+             \x20 - {name: tag, in: query, schema: {type: array, items: {type: string}}}\n\
+             responses: {}\n",
+        )
+        .unwrap();
+        let mut ctx = TypeContext::default();
+        gen_fn(
+            "/search",
+            "get",
+            &operation,
+            false,
+            &mut ctx,
+            &mut Vec::new(),
+        )
+        .unwrap();
+        assert_eq!(
+            ctx.warnings,
+            vec![
+                "Query parameter id of search is sent as repeated values instead of pipe-delimited ones",
+                "Query parameter q of search is sent as repeated values instead of tab-delimited ones",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_gen_fn_content_parameter() {
+        let operation: Operation = serde_yaml::from_str(
+            "operationId: dcim_devices_list\n\
+             parameters:\n\
+             \x20 - {name: limit, in: query, schema: {type: integer}}\n\
+             \x20 - {name: filter, in: query, content: {application/json: {schema: {type: object}}}}\n\
+             responses: {}\n",
+        )
+        .unwrap();
+        let mut ctx = TypeContext::default();
+        let result = gen_fn(
+            "/api/dcim/devices/",
+            "get",
+            &operation,
+            false,
+            &mut ctx,
+            &mut Vec::new(),
+        )
+        .unwrap();
+        assert!(result.contains("\tpub limit: Option<i64>,\n"));
+        assert!(!result.contains("filter"));
+        assert_eq!(
+            ctx.warnings,
+            vec![
+                "Parameter filter of dcim_devices_list is described by `content` and was skipped, only `schema` is supported"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_gen_fn_missing_media_schema() {
+        let operation: Operation = serde_yaml::from_str(
+            "responses:\n  '200':\n    description: ok\n    content:\n      application/json: {}\n",
+        )
+        .unwrap();
+        let result = gen_fn(
+            "/test",
+            "get",
+            &operation,
+            false,
+            &mut TypeContext::default(),
+            &mut Vec::new(),
+        );
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "#/paths/~1test/get/responses/200/content/application~1json: media type has no schema"
+        );
+    }
+
+    #[test]
+    fn test_gen_client() {
+        let signature = |name: &str| Signature {
+            name: name.to_owned(),
+            description: None,
+            args: vec![("id".to_owned(), "i64".to_owned())],
+            response: "Response".to_owned(),
+        };
+        let signatures = [
+            signature("dcim_devices_list"),
+            signature("devices_list"),
+            signature("dcim_type"),
+            signature("other_list"),
+        ];
+
+        let result = gen_client("dcim", &signatures, true);
+        assert!(result.contains("impl ThanixClient {\n"));
+        assert!(result.contains("\tpub fn dcim(&self) -> DcimApi<'_> {\n"));
+        // Names which would clash or aren't identifiers keep their prefix.
+        assert!(result.contains(
+            "\tpub async fn dcim_devices_list(&self, id: i64) -> Result<Response, Error> {\n\t\tdcim_devices_list(self.client, id).await\n"
+        ));
+        assert!(result.contains("\tpub async fn devices_list(&self, id: i64)"));
+        assert!(result.contains("\tpub async fn dcim_type(&self, id: i64)"));
+        assert!(result.contains("\tpub async fn other_list(&self, id: i64)"));
+    }
+
+    #[test]
+    fn test_gen_client_reserved() {
+        let result = gen_client("auth", &[], false);
+        // The client has an `auth` method of its own.
+        assert!(result.contains("see [`ThanixClient::auth_api`]"));
+        assert!(result.contains("\tpub fn auth_api(&self) -> AuthApi<'_> {\n"));
+        assert_eq!(accessor_name("new"), "new_api");
+        assert_eq!(accessor_name("users"), "users");
     }
 
     #[test]
@@ -0,0 +1,291 @@
+//! Resolve local `$ref`s before generating code.
+//!
+//! References to component schemas are kept, as they become the names of the generated types.
+//! Every other local reference, e.g. to `#/components/parameters/limit`, is replaced by a copy of
+//! its target, so the generators only ever see the parameters, request bodies, responses, headers
+//! and path items themselves. The parameters of path items are then copied into their operations.
+
+use std::collections::HashMap;
+
+use openapiv3::OpenAPI;
+use serde_yaml::Value;
+
+use crate::error::ThanixError;
+
+/// Resolve all local references of `api`, except the ones to component schemas.
+pub fn resolve_refs(api: &OpenAPI) -> Result<OpenAPI, ThanixError> {
+    let doc = serde_yaml::to_value(api).map_err(|e| ThanixError::schema(&[], e.to_string()))?;
+    serde_yaml::from_value(resolve_document(doc)?)
+        .map_err(|e| ThanixError::schema(&[], e.to_string()))
+}
+
+/// Resolve all local references of a raw OpenAPI document, except the ones to component schemas.
+///
+/// Unlike [`resolve_refs`] this also sees parts of the document which [`OpenAPI`] doesn't model,
+/// like `components.pathItems`.
+pub fn resolve_document(mut doc: Value) -> Result<Value, ThanixError> {
+    let root = doc.clone();
+    let mut resolver = Resolver {
+        root: &root,
+        resolved: HashMap::new(),
+        stack: Vec::new(),
+    };
+    resolver.inline(&mut doc)?;
+    inherit_parameters(&mut doc);
+    Ok(doc)
+}
+
+/// The keys of the operations of a path item.
+const METHODS: &[&str] = &[
+    "get", "put", "post", "delete", "options", "head", "patch", "trace",
+];
+
+/// Copy the parameters shared by all operations of a path item into each of them, as the
+/// generators only read the parameters of operations.
+///
+/// A parameter of the operation with the same name and location overrides the shared one.
+fn inherit_parameters(doc: &mut Value) {
+    let Some(Value::Mapping(paths)) = doc.get_mut("paths") else {
+        return;
+    };
+    for (_, item) in paths.iter_mut() {
+        let Value::Mapping(item) = item else {
+            continue;
+        };
+        let Some(Value::Sequence(shared)) = item.remove("parameters") else {
+            continue;
+        };
+        let key = |param: &Value| (param.get("name").cloned(), param.get("in").cloned());
+        for method in METHODS {
+            let Some(Value::Mapping(op)) = item.get_mut(*method) else {
+                continue;
+            };
+            let params = op
+                .entry("parameters".into())
+                .or_insert_with(|| Value::Sequence(Vec::new()));
+            let Value::Sequence(params) = params else {
+                continue;
+            };
+            let inherited: Vec<_> = shared
+                .iter()
+                .filter(|param| !params.iter().any(|p| key(p) == key(param)))
+                .cloned()
+                .collect();
+            params.splice(0..0, inherited);
+        }
+    }
+}
+
+/// Check whether `reference` names a component schema, like `#/components/schemas/Device`.
+fn is_schema_ref(reference: &str) -> bool {
+    reference
+        .strip_prefix("#/components/schemas/")
+        .is_some_and(|name| !name.contains('/'))
+}
+
+struct Resolver<'a> {
+    /// The unmodified document, which references point into.
+    root: &'a Value,
+    /// Already inlined targets, keyed by their reference.
+    resolved: HashMap<String, Value>,
+    /// The references currently being inlined, to detect cycles.
+    stack: Vec<String>,
+}
+
+impl Resolver<'_> {
+    /// Replace all references within `value` by their targets.
+    fn inline(&mut self, value: &mut Value) -> Result<(), ThanixError> {
+        match value {
+            Value::Mapping(map) => {
+                if let Some(Value::String(reference)) = map.get("$ref") {
+                    if !reference.starts_with('#') {
+                        // Those are rewritten when bundling the files, see `read_spec`.
+                        return Err(ThanixError::Schema {
+                            pointer: reference.clone(),
+                            message: "`$ref` into another file can only be resolved when reading the schema from a file".to_owned(),
+                        });
+                    }
+                    if !is_schema_ref(reference) {
+                        let reference = reference.clone();
+                        *value = self.target(&reference)?;
+                    }
+                    return Ok(());
+                }
+                for (_, v) in map.iter_mut() {
+                    self.inline(v)?;
+                }
+            }
+            Value::Sequence(items) => {
+                for v in items {
+                    self.inline(v)?;
+                }
+            }
+            Value::Tagged(tagged) => self.inline(&mut tagged.value)?,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Get the target of `reference`, with all references within it inlined.
+    fn target(&mut self, reference: &str) -> Result<Value, ThanixError> {
+        if let Some(target) = self.resolved.get(reference) {
+            return Ok(target.clone());
+        }
+        if let Some(start) = self.stack.iter().position(|r| r == reference) {
+            let mut cycle = self.stack[start..].to_vec();
+            cycle.push(reference.to_owned());
+            return Err(ThanixError::Schema {
+                pointer: reference.to_owned(),
+                message: format!("`$ref` cycle: {}", cycle.join(" -> ")),
+            });
+        }
+
+        let mut target =
+            pointer(self.root, reference)
+                .cloned()
+                .ok_or_else(|| ThanixError::Schema {
+                    pointer: reference.to_owned(),
+                    message: "`$ref` target doesn't exist".to_owned(),
+                })?;
+        self.stack.push(reference.to_owned());
+        self.inline(&mut target)?;
+        self.stack.pop();
+        self.resolved.insert(reference.to_owned(), target.clone());
+        Ok(target)
+    }
+}
+
+/// Look up a JSON pointer like `#/components/parameters/limit` in `root`.
+pub fn pointer<'a>(root: &'a Value, reference: &str) -> Option<&'a Value> {
+    let path = reference.strip_prefix('#')?;
+    if path.is_empty() {
+        return Some(root);
+    }
+    path.strip_prefix('/')?
+        .split('/')
+        .map(|segment| segment.replace("~1", "/").replace("~0", "~"))
+        .try_fold(root, |value, segment| match value {
+            Value::Mapping(map) => map.get(segment.as_str()),
+            Value::Sequence(items) => items.get(segment.parse::<usize>().ok()?),
+            _ => None,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolve(yaml: &str) -> Result<Value, ThanixError> {
+        resolve_document(serde_yaml::from_str(yaml).unwrap())
+    }
+
+    #[test]
+    fn test_resolve_parameter() {
+        let doc = resolve(
+            "paths:\n\
+             \x20 /api/devices/:\n\
+             \x20   get:\n\
+             \x20     parameters: [{$ref: '#/components/parameters/limit'}]\n\
+             components:\n\
+             \x20 parameters:\n\
+             \x20   limit: {name: limit, in: query, schema: {$ref: '#/components/schemas/Limit'}}\n",
+        )
+        .unwrap();
+        let param = &doc["paths"]["/api/devices/"]["get"]["parameters"][0];
+        assert_eq!(param["name"], "limit");
+        // References to component schemas are kept.
+        assert_eq!(param["schema"]["$ref"], "#/components/schemas/Limit");
+    }
+
+    #[test]
+    fn test_resolve_nested() {
+        let doc = resolve(
+            "paths:\n\
+             \x20 /a/: {$ref: '#/components/pathItems/a'}\n\
+             components:\n\
+             \x20 pathItems:\n\
+             \x20   a: {get: {responses: {'200': {$ref: '#/components/responses/ok'}}}}\n\
+             \x20 responses:\n\
+             \x20   ok: {description: OK, headers: {X-Total: {$ref: '#/components/headers/total'}}}\n\
+             \x20 headers:\n\
+             \x20   total: {schema: {type: integer}}\n",
+        )
+        .unwrap();
+        let response = &doc["paths"]["/a/"]["get"]["responses"]["200"];
+        assert_eq!(response["description"], "OK");
+        assert_eq!(response["headers"]["X-Total"]["schema"]["type"], "integer");
+    }
+
+    #[test]
+    fn test_resolve_path_parameters() {
+        let doc = resolve(
+            "paths:\n\
+             \x20 /a/{id}/: {$ref: '#/components/pathItems/a'}\n\
+             components:\n\
+             \x20 parameters:\n\
+             \x20   id: {name: id, in: path, required: true, schema: {type: integer}}\n\
+             \x20 pathItems:\n\
+             \x20   a:\n\
+             \x20     parameters:\n\
+             \x20       - $ref: '#/components/parameters/id'\n\
+             \x20       - {name: limit, in: query, schema: {type: integer}}\n\
+             \x20     get: {responses: {}}\n\
+             \x20     delete:\n\
+             \x20       parameters: [{name: limit, in: query, schema: {type: string}}]\n\
+             \x20       responses: {}\n",
+        )
+        .unwrap();
+        let item = &doc["paths"]["/a/{id}/"];
+        assert!(item.get("parameters").is_none());
+        let get = item["get"]["parameters"].as_sequence().unwrap();
+        assert_eq!(get.len(), 2);
+        assert_eq!(get[0]["name"], "id");
+        // The parameter of the operation overrides the shared one.
+        let delete = item["delete"]["parameters"].as_sequence().unwrap();
+        assert_eq!(delete.len(), 2);
+        assert_eq!(delete[0]["name"], "id");
+        assert_eq!(delete[1]["schema"]["type"], "string");
+    }
+
+    #[test]
+    fn test_resolve_escaped_pointer() {
+        let doc = resolve(
+            "paths:\n\
+             \x20 /a/: {get: {description: A}}\n\
+             \x20 /b/: {$ref: '#/paths/~1a~1'}\n",
+        )
+        .unwrap();
+        assert_eq!(doc["paths"]["/b/"]["get"]["description"], "A");
+    }
+
+    #[test]
+    fn test_resolve_external() {
+        let err = resolve("paths:\n  /a/: {$ref: 'a.yaml'}\n").unwrap_err();
+        assert!(matches!(err, ThanixError::Schema { pointer, .. } if pointer == "a.yaml"));
+    }
+
+    #[test]
+    fn test_resolve_cycle() {
+        let err = resolve(
+            "components:\n\
+             \x20 responses:\n\
+             \x20   a: {$ref: '#/components/responses/b'}\n\
+             \x20   b: {$ref: '#/components/responses/a'}\n",
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "#/components/responses/b: `$ref` cycle: #/components/responses/b -> \
+             #/components/responses/a -> #/components/responses/b"
+        );
+    }
+
+    #[test]
+    fn test_resolve_missing() {
+        let err = resolve("paths:\n  /a/: {$ref: '#/components/pathItems/a'}\n").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "#/components/pathItems/a: `$ref` target doesn't exist"
+        );
+    }
+}
@@ -0,0 +1,359 @@
+//! Split the generated code into one module per tag, e.g. `dcim` or `ipam`.
+//!
+//! Every operation belongs to the module named after its first tag, or after the first segment of
+//! its path if it has none, e.g. `status` for `/api/status/`. Every component schema belongs to the
+//! module whose operations use it. Schemas used by several modules, or by none, are put into the
+//! [`SHARED_MODULE`] together with everything they use.
+//!
+//! Each module becomes a feature of the generated crate, which enables the features of the modules
+//! whose types it uses.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use check_keyword::CheckKeyword;
+use convert_case::{Case, Casing};
+use openapiv3::{Components, Operation, PathItem};
+
+use crate::filter;
+
+/// Module of the types which aren't owned by a single other module.
+pub const SHARED_MODULE: &str = "shared";
+
+/// The operations of each module, keyed by module name.
+pub type Modules<'a> = BTreeMap<String, Vec<(&'a str, PathItem)>>;
+
+/// Get the name of the module the operation `op` at `path` belongs to.
+pub fn module_name(path: &str, op: &Operation) -> String {
+    let name = match op.tags.first() {
+        Some(tag) => tag.as_str(),
+        None => path
+            .split('/')
+            .filter(|s| !s.is_empty() && !s.starts_with('{'))
+            .find(|s| *s != "api")
+            .unwrap_or_default(),
+    };
+    let mut name: String = name
+        .to_case(Case::Snake)
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    if name.is_empty() {
+        name = "root".to_owned();
+    }
+    if name.starts_with(|c: char| c.is_ascii_digit()) || name.is_keyword() {
+        name.insert(0, '_');
+    }
+    // Operations tagged `shared` mustn't end up in the module of the shared types.
+    if name == SHARED_MODULE {
+        name = feature_name(&name);
+    }
+    name
+}
+
+/// Get the name of the feature which enables `module`.
+///
+/// Modules clashing with the fixed features of the generated crate get a suffix.
+pub fn feature_name(module: &str) -> String {
+    match module {
+        "default" | "blocking" | "debug_messages" | "full" | SHARED_MODULE => {
+            format!("{}_api", module)
+        }
+        _ => module.to_owned(),
+    }
+}
+
+/// Split path items into the modules their operations belong to.
+///
+/// A path item whose operations have different tags is split into several items.
+pub fn group<'a>(items: &[(&'a str, PathItem)]) -> Modules<'a> {
+    let mut modules = Modules::new();
+    for (path, item) in items {
+        // All operations are removed from a copy, then the ones of each module added back.
+        let mut empty = item.clone();
+        let operations = [
+            &mut empty.get,
+            &mut empty.put,
+            &mut empty.post,
+            &mut empty.delete,
+            &mut empty.options,
+            &mut empty.head,
+            &mut empty.patch,
+            &mut empty.trace,
+        ];
+        for op in operations {
+            *op = None;
+        }
+
+        let mut split: BTreeMap<String, PathItem> = BTreeMap::new();
+        let mut add = |op: &Option<Operation>,
+                       field: fn(&mut PathItem) -> &mut Option<Operation>| {
+            if let Some(op) = op {
+                let module = split
+                    .entry(module_name(path, op))
+                    .or_insert_with(|| empty.clone());
+                *field(module) = Some(op.clone());
+            }
+        };
+        add(&item.get, |i| &mut i.get);
+        add(&item.put, |i| &mut i.put);
+        add(&item.post, |i| &mut i.post);
+        add(&item.delete, |i| &mut i.delete);
+        add(&item.options, |i| &mut i.options);
+        add(&item.head, |i| &mut i.head);
+        add(&item.patch, |i| &mut i.patch);
+        add(&item.trace, |i| &mut i.trace);
+
+        for (module, item) in split {
+            modules.entry(module).or_default().push((path, item));
+        }
+    }
+    modules
+}
+
+/// Which module each type belongs to, and which modules need the types of others.
+#[derive(Debug, Default)]
+pub struct Layout {
+    /// The module of every component schema, keyed by schema name.
+    pub owners: HashMap<String, String>,
+    /// The other modules each module uses types of, without the [`SHARED_MODULE`].
+    pub dependencies: BTreeMap<String, BTreeSet<String>>,
+}
+
+/// Decide which module each component schema belongs to.
+///
+/// A schema used by several modules belongs to the one using it directly in its operations, if
+/// there is only one. E.g. `Tenant` belongs to `tenancy`, even though `dcim` uses it through
+/// `Device`. Modules must not depend on each other in a cycle, so the types causing one are
+/// shared instead.
+pub fn layout(components: &Components, modules: &Modules) -> Layout {
+    // The schemas every module uses directly and the ones it uses at all.
+    let mut direct: HashMap<&str, BTreeSet<&str>> = HashMap::new();
+    let mut used: BTreeMap<&str, BTreeSet<String>> = BTreeMap::new();
+    let mut users: HashMap<String, BTreeSet<&str>> = HashMap::new();
+    for (module, items) in modules {
+        let mut names = Vec::new();
+        for (_, item) in items {
+            if let Ok(value) = serde_yaml::to_value(item) {
+                filter::schema_refs(&value, &mut names);
+            }
+        }
+        for name in &names {
+            if let Some((key, _)) = components.schemas.get_key_value(name) {
+                direct.entry(key).or_default().insert(module);
+            }
+        }
+        let reachable = filter::reachable_schemas(components, names);
+        for name in &reachable {
+            users.entry(name.clone()).or_default().insert(module);
+        }
+        used.insert(module, reachable);
+    }
+
+    let mut layout = Layout::default();
+    let mut shared = Vec::new();
+    for name in components.schemas.keys() {
+        let owner = match users.get(name) {
+            Some(users) if users.len() == 1 => users.first().copied(),
+            Some(_) => direct
+                .get(name.as_str())
+                .filter(|direct| direct.len() == 1)
+                .and_then(|direct| direct.first().copied()),
+            None => None,
+        };
+        match owner {
+            Some(owner) => {
+                layout.owners.insert(name.clone(), owner.to_owned());
+            }
+            None => shared.push(name.clone()),
+        }
+    }
+
+    loop {
+        // Shared types can't depend on the types of any single module.
+        for name in filter::reachable_schemas(components, shared) {
+            layout.owners.insert(name, SHARED_MODULE.to_owned());
+        }
+        layout.dependencies = used
+            .iter()
+            .map(|(module, names)| {
+                let dependencies = names
+                    .iter()
+                    .filter_map(|name| layout.owners.get(name))
+                    .filter(|owner| owner != module && *owner != SHARED_MODULE)
+                    .cloned()
+                    .collect();
+                (module.to_string(), dependencies)
+            })
+            .collect();
+
+        let Some(cycle) = find_cycle(&layout.dependencies) else {
+            return layout;
+        };
+        // Share the types every module of the cycle uses of the next one.
+        shared = cycle
+            .windows(2)
+            .flat_map(|edge| {
+                used[edge[0].as_str()]
+                    .iter()
+                    .filter(|name| layout.owners.get(*name) == Some(&edge[1]))
+                    .cloned()
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+    }
+}
+
+/// Find a cycle in `dependencies`, as the modules along it with the first one repeated at the end.
+fn find_cycle(dependencies: &BTreeMap<String, BTreeSet<String>>) -> Option<Vec<String>> {
+    fn visit(
+        module: &String,
+        dependencies: &BTreeMap<String, BTreeSet<String>>,
+        path: &mut Vec<String>,
+        done: &mut BTreeSet<String>,
+    ) -> Option<Vec<String>> {
+        if let Some(start) = path.iter().position(|m| m == module) {
+            let mut cycle = path[start..].to_vec();
+            cycle.push(module.clone());
+            return Some(cycle);
+        }
+        if done.contains(module) {
+            return None;
+        }
+        path.push(module.clone());
+        for dependency in dependencies.get(module).into_iter().flatten() {
+            if let Some(cycle) = visit(dependency, dependencies, path, done) {
+                return Some(cycle);
+            }
+        }
+        path.pop();
+        done.insert(module.clone());
+        None
+    }
+
+    let mut done = BTreeSet::new();
+    dependencies
+        .keys()
+        .find_map(|module| visit(module, dependencies, &mut Vec::new(), &mut done))
+}
+
+#[cfg(test)]
+mod tests {
+    use openapiv3::OpenAPI;
+
+    use super::*;
+
+    static SPEC: &str = "openapi: 3.0.3\n\
+        info: {title: Test, version: '1'}\n\
+        paths:\n\
+        \x20 /api/dcim/devices/:\n\
+        \x20   get:\n\
+        \x20     tags: [dcim]\n\
+        \x20     responses: {'200': {description: OK, content: {application/json: {schema: {$ref: '#/components/schemas/Device'}}}}}\n\
+        \x20   post:\n\
+        \x20     tags: [DCIM Extras]\n\
+        \x20     responses: {'200': {description: OK, content: {application/json: {schema: {$ref: '#/components/schemas/Tag'}}}}}\n\
+        \x20 /api/tenancy/tenants/:\n\
+        \x20   get:\n\
+        \x20     tags: [tenancy]\n\
+        \x20     responses: {'200': {description: OK, content: {application/json: {schema: {$ref: '#/components/schemas/Tenant'}}}}}\n\
+        \x20 /api/status/:\n\
+        \x20   get:\n\
+        \x20     responses: {'200': {description: OK, content: {application/json: {schema: {$ref: '#/components/schemas/Tag'}}}}}\n\
+        components:\n\
+        \x20 schemas:\n\
+        \x20   Device: {type: object, properties: {tenant: {$ref: '#/components/schemas/Tenant'}}}\n\
+        \x20   Tenant: {type: object, properties: {tags: {type: array, items: {$ref: '#/components/schemas/Tag'}}}}\n\
+        \x20   Tag: {type: object, properties: {color: {$ref: '#/components/schemas/Color'}}}\n\
+        \x20   Color: {type: string}\n\
+        \x20   Unused: {type: string}\n";
+
+    fn modules(api: &OpenAPI) -> Modules<'_> {
+        let items: Vec<(&str, PathItem)> = api
+            .paths
+            .iter()
+            .map(|(path, item)| (path.as_str(), item.as_item().unwrap().clone()))
+            .collect();
+        group(&items)
+    }
+
+    #[test]
+    fn test_module_name() {
+        let mut op = Operation::default();
+        assert_eq!(module_name("/api/status/", &op), "status");
+        assert_eq!(module_name("/{id}/", &op), "root");
+        op.tags = vec!["Pet Store".to_owned()];
+        assert_eq!(module_name("/api/pets/", &op), "pet_store");
+        op.tags = vec!["type".to_owned()];
+        assert_eq!(module_name("/api/pets/", &op), "_type");
+        op.tags = vec!["shared".to_owned()];
+        assert_eq!(module_name("/api/pets/", &op), "shared_api");
+        op.tags = Vec::new();
+        assert_eq!(module_name("/api/shared/", &op), "shared_api");
+    }
+
+    #[test]
+    fn test_feature_name() {
+        assert_eq!(feature_name("dcim"), "dcim");
+        assert_eq!(feature_name("full"), "full_api");
+        assert_eq!(feature_name("shared"), "shared_api");
+    }
+
+    #[test]
+    fn test_group() {
+        let api: OpenAPI = serde_yaml::from_str(SPEC).unwrap();
+        let modules = modules(&api);
+        let names: Vec<&String> = modules.keys().collect();
+        assert_eq!(names, ["dcim", "dcim_extras", "status", "tenancy"]);
+
+        let (path, item) = &modules["dcim"][0];
+        assert_eq!(*path, "/api/dcim/devices/");
+        assert!(item.get.is_some() && item.post.is_none());
+        assert!(modules["dcim_extras"][0].1.post.is_some());
+    }
+
+    #[test]
+    fn test_layout() {
+        let api: OpenAPI = serde_yaml::from_str(SPEC).unwrap();
+        let layout = layout(api.components.as_ref().unwrap(), &modules(&api));
+        let owners = &layout.owners;
+        assert_eq!(owners["Device"], "dcim");
+        // Used by `dcim` through `Device`, but only `tenancy` returns it.
+        assert_eq!(owners["Tenant"], "tenancy");
+        // Returned by several modules, and so is everything it uses.
+        assert_eq!(owners["Tag"], SHARED_MODULE);
+        assert_eq!(owners["Color"], SHARED_MODULE);
+        assert_eq!(owners["Unused"], SHARED_MODULE);
+
+        assert_eq!(
+            layout.dependencies["dcim"],
+            BTreeSet::from(["tenancy".to_owned()])
+        );
+        assert!(layout.dependencies["tenancy"].is_empty());
+    }
+
+    #[test]
+    fn test_layout_cycle() {
+        let api: OpenAPI = serde_yaml::from_str(
+            "openapi: 3.0.3\n\
+             info: {title: Test, version: '1'}\n\
+             paths:\n\
+             \x20 /api/dcim/devices/:\n\
+             \x20   get:\n\
+             \x20     tags: [dcim]\n\
+             \x20     responses: {'200': {description: OK, content: {application/json: {schema: {$ref: '#/components/schemas/Device'}}}}}\n\
+             \x20 /api/tenancy/tenants/:\n\
+             \x20   get:\n\
+             \x20     tags: [tenancy]\n\
+             \x20     responses: {'200': {description: OK, content: {application/json: {schema: {$ref: '#/components/schemas/Tenant'}}}}}\n\
+             components:\n\
+             \x20 schemas:\n\
+             \x20   Device: {type: object, properties: {tenant: {$ref: '#/components/schemas/Tenant'}}}\n\
+             \x20   Tenant: {type: object, properties: {devices: {type: array, items: {$ref: '#/components/schemas/Device'}}}}\n",
+        )
+        .unwrap();
+        let layout = layout(api.components.as_ref().unwrap(), &modules(&api));
+        assert_eq!(layout.owners["Device"], SHARED_MODULE);
+        assert_eq!(layout.owners["Tenant"], SHARED_MODULE);
+        assert!(layout.dependencies.values().all(BTreeSet::is_empty));
+    }
+}
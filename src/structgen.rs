@@ -1,102 +1,212 @@
 //! Generate structs from API objects.
 
-use crate::bindgen;
+use crate::bindgen::{self, TypeContext};
+use crate::util;
 use check_keyword::CheckKeyword;
-use openapiv3::{ReferenceOr, Schema, SchemaKind, Type};
+use convert_case::{Case, Casing};
+use openapiv3::{AdditionalProperties, ObjectType, ReferenceOr, Schema, SchemaKind, Type};
 
 /// Generate the structs to be used as API request payloads.
 ///
-/// If `workaround_mode` is enabled, will check if the current struct matches with the names listed
-/// in `unsanitary_data` and make all fields of these structs optional.
+/// Members of an `allOf` are merged into a single struct, see [`merge_all_of`].
+///
+/// If the schema allows `additionalProperties`, they are kept in a flattened `extra` map, so
+/// unknown keys survive a round trip.
+///
+/// Properties which are not `required` become `Option`s that are skipped when serializing. If they
+/// are also `nullable`, they become an `Option<Option<T>>`, where `Some(None)` is an explicit
+/// `null`.
+///
+/// If `workaround_mode` is enabled, will check if the current struct or field matches one of the
+/// `--unsanitary` patterns and make the matching fields optional.
 /// This can help when normally generated API clients crash with serialization issues due to
 /// NetBox's response data having some fileds set to `null`, despite the YAML stating that they are
 /// not nullable.
 ///
 /// > [!Note]
 /// > The workaround mentioned above is *only* valid and useful when creating an API client with
-/// NetBox.
+/// > NetBox.
 /// > Using the `--workaround` flag with any other use case is **not advised** because it weakens
-/// data validation.
+/// > data validation.
 ///
 /// # Parameters
 ///
 /// * `name: &str` - The name of the struct to generate.
 /// * `schema: &Schema` - The schema this struct follows.
-/// * `workaround_mode: bool` - Whether `--workaround` flag has been set or not.
+/// * `ctx: &mut TypeContext` - Holds whether the `--workaround` flag has been set and receives the
+///   inline types hoisted out of this struct.
 ///
 /// # Returns
 ///
 /// * `Option<String>` - The string represnetation of the given struct.
-pub fn generate(name: &str, schema: &Schema, workaround_mode: bool) -> Option<String> {
+pub fn generate(name: &str, schema: &Schema, ctx: &mut TypeContext) -> Option<String> {
+    // Members which can't be merged into the struct are flattened into it.
+    let mut flattened = Vec::new();
+    let merged;
     let typ = match &schema.schema_kind {
         SchemaKind::Type(x) => x,
+        SchemaKind::AllOf { all_of } if !all_of.is_empty() => {
+            let mut obj = ObjectType::default();
+            let mut skipped = Vec::new();
+            merge_all_of(
+                all_of,
+                ctx,
+                &mut obj,
+                &mut flattened,
+                &mut skipped,
+                &mut Vec::new(),
+            );
+            for member in skipped {
+                ctx.warnings.push(format!(
+                    "{} of the allOf of {} isn't an object and was skipped",
+                    member, name
+                ));
+            }
+            merged = Type::Object(obj);
+            &merged
+        }
+        SchemaKind::OneOf { one_of: members } | SchemaKind::AnyOf { any_of: members }
+            if !members.is_empty() =>
+        {
+            return Some(generate_union(name, schema, members, ctx));
+        }
         _ => return None,
     };
 
+    // Enumerated strings become an enum instead of a struct.
+    if let Type::String(x) = typ
+        && x.enumeration.iter().any(Option::is_some)
+    {
+        return Some(generate_enum(
+            name,
+            &x.enumeration,
+            enum_default(schema),
+            schema.schema_data.description.clone(),
+            ctx.enum_fallback,
+        ));
+    }
+
+    // Structs with a required field of a type like a union or `url::Url` can't implement `Default`.
+    let default = ctx.struct_implements_default(name, schema);
+
     // Assemble struct string.
-    let mut result =
-        "#[derive(Serialize, Deserialize, Debug, Default, Clone)]\npub struct ".to_owned();
-    result += name;
+    let mut result = format!(
+        "#[derive(Serialize, Deserialize, Debug, {}Clone)]\npub struct {}",
+        if default { "Default, " } else { "" },
+        name
+    );
 
     // If not an ObjectType or an Array of objects, return None.
     match &typ {
         Type::Object(obj) => {
             result += " {\n";
+            // The names of all fields, to keep the one of the extra properties unique.
+            let mut fields = Vec::new();
 
             // For every component property.
             for (prop_name, prop) in &obj.properties {
                 let p = prop.clone().unbox();
                 // Assemble a field declaration in the struct.
-                let type_name = bindgen::type_to_string(&p);
+                let type_name = bindgen::type_to_string(
+                    &p,
+                    &format!("{}{}", name, prop_name.to_case(Case::Pascal)),
+                    ctx,
+                );
 
                 // If the property has a description, prepend a doc string.
-                if let ReferenceOr::Item(item) = &p {
-                    if let Some(desc) = &item.schema_data.description {
-                        result += bindgen::make_comment(Some(desc.clone()), 1).as_str();
-                    }
+                if let ReferenceOr::Item(item) = &p
+                    && let Some(desc) = &item.schema_data.description
+                {
+                    result += bindgen::make_comment(Some(desc.clone()), 1).as_str();
                 }
-                result += "\t";
-
+                let nullable = matches!(&p, ReferenceOr::Item(item) if item.schema_data.nullable);
                 // Patch requests need to accept partial data.
-                if name.starts_with("Patched") {
-                    result += "#[serde(skip_serializing_if = \"Option::is_none\")]\n\t";
-                }
+                let optional = !obj.required.contains(prop_name) || name.starts_with("Patched");
 
-                result += "pub ";
-                result += &prop_name.clone().into_safe();
-                result += ": ";
-
-                // The NetBox schema may be incorrect and we can't rely on what we get as a response.
-                // Therefore, we must make every response field nullable, even if it's technically not correct.
-                let typ = if name.starts_with("Patched") {
+                let typ = if optional && nullable {
+                    // Distinguish between an absent field (`None`) and an explicit `null` (`Some(None)`).
+                    result += "\t#[serde(default, skip_serializing_if = \"Option::is_none\", deserialize_with = \"crate::util::deserialize_some\")]\n";
                     format!("Option<{}>", type_name)
-                } else if workaround_mode
+                } else if optional {
+                    result += "\t#[serde(default, skip_serializing_if = \"Option::is_none\")]\n";
+                    if type_name.starts_with("Option<") {
+                        type_name
+                    } else {
+                        format!("Option<{}>", type_name)
+                    }
+                }
+                // The NetBox schema may be incorrect and we can't rely on what we get as a response.
+                // Therefore, we must make the affected response fields nullable, even if it's technically not correct.
+                else if ctx.workaround_mode
                     && !name.ends_with("Request")
                     && !type_name.contains("Option")
-                    && prop_name != "id"
+                    && util::is_unsanitary(&ctx.unsanitary, name, prop_name)
                 {
                     format!("Option<{}>", type_name)
                 } else {
                     type_name
                 };
 
+                let field_name = prop_name.clone().into_safe();
+                result += "\tpub ";
+                result += &field_name;
+                result += ": ";
                 result += &typ;
                 result += ",\n";
+                fields.push(field_name);
+            }
+
+            for (i, member) in flattened.iter().enumerate() {
+                let type_name =
+                    bindgen::type_to_string(member, &format!("{}AllOf{}", name, i + 1), ctx);
+                let mut field_name = type_name.to_case(Case::Snake).into_safe();
+                while fields.contains(&field_name) {
+                    field_name += "_";
+                }
+                result += &format!(
+                    "\t#[serde(flatten)]\n\tpub {}: {},\n",
+                    field_name, type_name
+                );
+                fields.push(field_name);
+            }
+
+            // Collect all other keys in a map.
+            let extra_type = match &obj.additional_properties {
+                Some(AdditionalProperties::Schema(value)) => Some(bindgen::type_to_string(
+                    value,
+                    &format!("{}Extra", name),
+                    ctx,
+                )),
+                Some(AdditionalProperties::Any(true)) => Some("serde_json::Value".to_owned()),
+                _ => None,
+            };
+            if let Some(value) = extra_type {
+                let mut field_name = "extra".to_owned();
+                while fields.contains(&field_name) {
+                    field_name += "_";
+                }
+                let typ = ctx.map_type(&value);
+                result += &format!("\t#[serde(flatten)]\n\tpub {}: {},\n", field_name, typ);
+                fields.push(field_name);
             }
 
             result += "}\n";
         }
         Type::Array(obj) => {
-            let p = obj.items.clone().unwrap().clone().unbox();
-            // Assemble a field declaration in the struct.
-            let type_name = bindgen::type_to_string(&p);
-
-            // If the property has a description, prepend a doc string.
-            if let ReferenceOr::Item(item) = &p {
-                if let Some(desc) = &item.schema_data.description {
-                    result += bindgen::make_comment(Some(desc.clone()), 1).as_str();
+            let type_name = match &obj.items {
+                Some(items) => {
+                    let p = items.clone().unbox();
+                    // If the property has a description, prepend a doc string.
+                    if let ReferenceOr::Item(item) = &p
+                        && let Some(desc) = &item.schema_data.description
+                    {
+                        result += bindgen::make_comment(Some(desc.clone()), 1).as_str();
+                    }
+                    bindgen::type_to_string(&p, &format!("{}Item", name), ctx)
                 }
-            }
+                // Items of any type.
+                None => "serde_json::Value".to_owned(),
+            };
             result += "(pub ";
             result += &type_name;
             result += ");\n";
@@ -109,6 +219,325 @@ pub fn generate(name: &str, schema: &Schema, workaround_mode: bool) -> Option<St
     Some(result)
 }
 
+/// Merge the members of an `allOf` into a single object.
+///
+/// Referenced members are resolved against the components of the schema, nested `allOf`s are
+/// merged as well. Members without a `type`, e.g. ones only listing `required` properties, are
+/// merged like objects. A `oneOf` or `anyOf` can't be merged and is collected in `flattened`
+/// instead, as is anything we can't look into. Members of any other type can't be part of an
+/// object at all and are collected in `skipped`.
+///
+/// # Parameters
+///
+/// * `members: &[ReferenceOr<Schema>]` - The members of the `allOf`.
+/// * `ctx: &TypeContext` - Holds the components to resolve references with.
+/// * `obj: &mut ObjectType` - Receives the properties of all members.
+/// * `flattened: &mut Vec<ReferenceOr<Schema>>` - Receives the members which can't be merged.
+/// * `skipped: &mut Vec<String>` - Receives a description of every member which isn't an object.
+/// * `visited: &mut Vec<String>` - The references already merged, to break reference cycles.
+pub fn merge_all_of(
+    members: &[ReferenceOr<Schema>],
+    ctx: &TypeContext,
+    obj: &mut ObjectType,
+    flattened: &mut Vec<ReferenceOr<Schema>>,
+    skipped: &mut Vec<String>,
+    visited: &mut Vec<String>,
+) {
+    for member in members {
+        let schema = match member {
+            ReferenceOr::Reference { reference } => {
+                if visited.contains(reference) {
+                    continue;
+                }
+                visited.push(reference.clone());
+                let resolved = reference
+                    .strip_prefix("#/components/schemas/")
+                    .and_then(|name| ctx.components.schemas.get(name));
+                match resolved {
+                    Some(ReferenceOr::Item(schema)) => schema,
+                    // Anything we can't look into is flattened as-is.
+                    _ => {
+                        flattened.push(member.clone());
+                        continue;
+                    }
+                }
+            }
+            ReferenceOr::Item(schema) => schema,
+        };
+
+        match &schema.schema_kind {
+            SchemaKind::Type(Type::Object(x)) => merge_object(obj, x),
+            SchemaKind::Any(x)
+                if x.typ.as_deref().is_none_or(|t| t == "object")
+                    && x.items.is_none()
+                    && x.enumeration.is_empty() =>
+            {
+                merge_object(
+                    obj,
+                    &ObjectType {
+                        properties: x.properties.clone(),
+                        required: x.required.clone(),
+                        additional_properties: x.additional_properties.clone(),
+                        ..Default::default()
+                    },
+                )
+            }
+            SchemaKind::AllOf { all_of } => {
+                merge_all_of(all_of, ctx, obj, flattened, skipped, visited)
+            }
+            SchemaKind::OneOf { .. } | SchemaKind::AnyOf { .. } => flattened.push(member.clone()),
+            _ => skipped.push(match member {
+                ReferenceOr::Reference { reference } => {
+                    format!("`{}`", reference.replace("#/components/schemas/", ""))
+                }
+                ReferenceOr::Item(_) => "An inline member".to_owned(),
+            }),
+        }
+    }
+}
+
+/// Add the properties of `x` to `obj`.
+fn merge_object(obj: &mut ObjectType, x: &ObjectType) {
+    for (prop_name, prop) in &x.properties {
+        obj.properties.insert(prop_name.clone(), prop.clone());
+    }
+    for required in &x.required {
+        if !obj.required.contains(required) {
+            obj.required.push(required.clone());
+        }
+    }
+    if obj.additional_properties.is_none() {
+        obj.additional_properties = x.additional_properties.clone();
+    }
+}
+
+/// Get the `default` of an enumerated string schema, if it's one of its values.
+pub fn enum_default(schema: &Schema) -> Option<&str> {
+    let SchemaKind::Type(Type::String(x)) = &schema.schema_kind else {
+        return None;
+    };
+    let default = schema.schema_data.default.as_ref()?.as_str()?;
+    x.enumeration
+        .iter()
+        .flatten()
+        .any(|value| value == default)
+        .then_some(default)
+}
+
+/// Generate an enum from the values of an enumerated string schema.
+///
+/// Every variant is renamed to its original value, `null` values are skipped as they are covered
+/// by the field being nullable. The enum only implements `Default` if the schema declares one.
+///
+/// # Parameters
+///
+/// * `name: &str` - The name of the enum to generate.
+/// * `values: &[Option<String>]` - The values listed in the schema.
+/// * `default: Option<&str>` - The default value of the schema, see [`enum_default`].
+/// * `description: Option<String>` - The description of the schema.
+/// * `fallback: bool` - Whether to add an `Unknown` variant catching all other values.
+///
+/// # Returns
+///
+/// * `String` - The string representation of the given enum.
+pub fn generate_enum(
+    name: &str,
+    values: &[Option<String>],
+    default: Option<&str>,
+    description: Option<String>,
+    fallback: bool,
+) -> String {
+    let mut result = bindgen::make_comment(description, 0);
+    result += &format!(
+        "#[derive(Serialize, Deserialize, Debug, {}Clone, PartialEq, Eq, Hash)]\npub enum ",
+        if default.is_some() { "Default, " } else { "" }
+    );
+    result += name;
+    result += " {\n";
+
+    let mut variants = Vec::new();
+    let mut default = default;
+    for value in values.iter().flatten() {
+        let variant = unique_variant_name(make_variant_name(value, variants.len()), &variants);
+        if default == Some(value.as_str()) {
+            result += "\t#[default]\n";
+            default = None;
+        }
+        result += &format!("\t#[serde(rename = {:?})]\n\t{},\n", value, variant);
+        variants.push(variant);
+    }
+
+    if fallback {
+        let variant = unique_variant_name("Unknown".to_owned(), &variants);
+        result += &format!("\t#[serde(other)]\n\t{},\n", variant);
+    }
+
+    result += "}\n";
+    result
+}
+
+/// Generate an enum from the members of a `oneOf` or `anyOf` schema.
+///
+/// If the schema has a `discriminator`, the enum has one variant per `mapping` entry, plus one per
+/// referenced member which isn't mapped explicitly. Its property is part of the members as well, so
+/// variants are serialized as their member and deserialized by the member the property names.
+/// Inline members can't be named by the property and are skipped with a warning.
+/// Otherwise the enum is untagged and `serde` picks the first member that matches.
+///
+/// # Parameters
+///
+/// * `name: &str` - The name of the enum to generate.
+/// * `schema: &Schema` - The schema containing the `oneOf` or `anyOf`.
+/// * `members: &[ReferenceOr<Schema>]` - The members of the `oneOf` or `anyOf`.
+/// * `ctx: &mut TypeContext` - Receives the inline types hoisted out of the members and the
+///   warnings about skipped ones.
+///
+/// # Returns
+///
+/// * `String` - The string representation of the given enum.
+pub fn generate_union(
+    name: &str,
+    schema: &Schema,
+    members: &[ReferenceOr<Schema>],
+    ctx: &mut TypeContext,
+) -> String {
+    let mut result = bindgen::make_comment(schema.schema_data.description.clone(), 0);
+
+    // Tuples of (tag value, variant name, variant type).
+    let mut variants: Vec<(Option<String>, String, String)> = Vec::new();
+    let mut taken = Vec::new();
+
+    match &schema.schema_data.discriminator {
+        Some(discriminator) => {
+            result += "#[derive(Serialize, Debug, Clone)]\n";
+            let mut mapped = Vec::new();
+            for (value, target) in &discriminator.mapping {
+                let reference = if target.contains('/') {
+                    target.clone()
+                } else {
+                    format!("#/components/schemas/{}", target)
+                };
+                mapped.push(reference.clone());
+                let typ = bindgen::type_to_string(&ReferenceOr::Reference { reference }, name, ctx);
+                let variant = unique_variant_name(make_variant_name(value, taken.len()), &taken);
+                taken.push(variant.clone());
+                variants.push((Some(value.clone()), variant, typ));
+            }
+            // Members without an explicit mapping are tagged with their schema name.
+            for (i, member) in members.iter().enumerate() {
+                match member {
+                    ReferenceOr::Reference { reference } if !mapped.contains(reference) => {
+                        let typ = bindgen::type_to_string(member, name, ctx);
+                        let variant = unique_variant_name(typ.clone(), &taken);
+                        taken.push(variant.clone());
+                        variants.push((Some(typ.clone()), variant, typ));
+                    }
+                    ReferenceOr::Reference { .. } => {}
+                    ReferenceOr::Item(_) => ctx.warnings.push(format!(
+                        "Member {} of {} has no discriminator value and was skipped",
+                        i + 1,
+                        name
+                    )),
+                }
+            }
+        }
+        None => {
+            result += "#[derive(Serialize, Deserialize, Debug, Clone)]\n";
+            for (i, member) in members.iter().enumerate() {
+                let typ =
+                    bindgen::type_to_string(member, &format!("{}Variant{}", name, i + 1), ctx);
+                // Name variants after their type if it's a plain (possibly qualified) identifier.
+                let base = typ.rsplit("::").next().unwrap_or_default();
+                let variant = if matches!(member, ReferenceOr::Reference { .. })
+                    || (base.chars().all(|c| c.is_ascii_alphanumeric()) && !typ.starts_with(name))
+                {
+                    base.to_case(Case::Pascal)
+                } else {
+                    format!("Variant{}", i + 1)
+                };
+                let variant = unique_variant_name(variant, &taken);
+                taken.push(variant.clone());
+                variants.push((None, variant, typ));
+            }
+        }
+    }
+
+    result += "#[serde(untagged)]\npub enum ";
+    result += name;
+    result += " {\n";
+    for (_, variant, typ) in &variants {
+        result += &format!("\t{}({}),\n", variant, typ);
+    }
+    result += "}\n";
+
+    if let Some(discriminator) = &schema.schema_data.discriminator {
+        result += &gen_discriminated_deserialize(name, &discriminator.property_name, &variants);
+    }
+    result
+}
+
+/// Generate the `Deserialize` implementation of a union with a discriminator, which picks the
+/// variant by the value of `property` and leaves the property in place for the member.
+fn gen_discriminated_deserialize(
+    name: &str,
+    property: &str,
+    variants: &[(Option<String>, String, String)],
+) -> String {
+    let mut result = format!(
+        "impl<'de> serde::Deserialize<'de> for {} {{\n\
+         \tfn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {{\n\
+         \t\tlet value = <serde_json::Value as serde::Deserialize>::deserialize(deserializer)?;\n\
+         \t\tmatch value.get({:?}).and_then(serde_json::Value::as_str) {{\n",
+        name, property
+    );
+    let mut tags = Vec::new();
+    for (tag, variant, _) in variants {
+        let tag = tag.as_deref().unwrap_or_default();
+        result += &format!(
+            "\t\t\tSome({:?}) => serde_json::from_value(value).map(Self::{}),\n",
+            tag, variant
+        );
+        tags.push(format!("{:?}", tag));
+    }
+    result += &format!(
+        "\t\t\tSome(tag) => return Err(serde::de::Error::unknown_variant(tag, &[{}])),\n\
+         \t\t\tNone => return Err(serde::de::Error::missing_field({:?})),\n\
+         \t\t}}\n\
+         \t\t.map_err(serde::de::Error::custom)\n\
+         \t}}\n\
+         }}\n",
+        tags.join(", "),
+        property
+    );
+    result
+}
+
+/// Turn an enum value into a valid variant name, e.g. `1000base-t` into `V1000BaseT`.
+fn make_variant_name(value: &str, index: usize) -> String {
+    let sanitized = value
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect::<String>()
+        .to_case(Case::Pascal);
+    match sanitized.chars().next() {
+        None if value.is_empty() => "Empty".to_owned(),
+        None => format!("Value{}", index),
+        Some(c) if c.is_ascii_digit() => format!("V{}", sanitized),
+        Some(_) => sanitized,
+    }
+}
+
+/// Append a number to `variant` until it doesn't collide with any of `taken`.
+fn unique_variant_name(variant: String, taken: &[String]) -> String {
+    let mut result = variant.clone();
+    let mut counter = 1;
+    while taken.contains(&result) {
+        counter += 1;
+        result = format!("{}{}", variant, counter);
+    }
+    result
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -122,9 +551,324 @@ mod tests {
                 ..Default::default()
             })), // Not an object
         };
-        let result = generate("InvalidStruct", &schema, false);
+        let result = generate("InvalidStruct", &schema, &mut TypeContext::default());
         assert_eq!(result, None);
     }
 
+    #[test]
+    fn test_generate_enum_schema() {
+        let schema = Schema {
+            schema_data: Default::default(),
+            schema_kind: SchemaKind::Type(Type::String(StringType {
+                enumeration: vec![Some("active".to_owned()), Some("planned".to_owned())],
+                ..Default::default()
+            })),
+        };
+        let result = generate("Status", &schema, &mut TypeContext::default()).unwrap();
+        assert!(result.contains("pub enum Status {"));
+        assert!(result.contains("\t#[serde(rename = \"active\")]\n\tActive,\n"));
+        assert!(result.contains("\t#[serde(rename = \"planned\")]\n\tPlanned,\n"));
+        assert!(!result.contains("#[serde(other)]"));
+        // Without a default in the schema, there is none in Rust either.
+        assert!(!result.contains("Default"));
+    }
+
+    #[test]
+    fn test_generate_enum_default() {
+        let schema: Schema =
+            serde_yaml::from_str("type: string\nenum: [active, planned]\ndefault: planned\n")
+                .unwrap();
+        let result = generate("Status", &schema, &mut TypeContext::default()).unwrap();
+        assert!(result.contains("#[derive(Serialize, Deserialize, Debug, Default, Clone,"));
+        assert!(result.contains("\t#[serde(rename = \"active\")]\n\tActive,\n"));
+        assert!(result.contains("\t#[default]\n\t#[serde(rename = \"planned\")]\n\tPlanned,\n"));
+
+        // A default which isn't one of the values is ignored.
+        let schema: Schema =
+            serde_yaml::from_str("type: string\nenum: [active]\ndefault: planned\n").unwrap();
+        assert_eq!(enum_default(&schema), None);
+    }
+
+    #[test]
+    fn test_generate_enum_fallback() {
+        let values = vec![Some("unknown".to_owned())];
+        let result = generate_enum("Status", &values, None, None, true);
+        assert!(result.contains("\t#[serde(rename = \"unknown\")]\n\tUnknown,\n"));
+        assert!(result.contains("\t#[serde(other)]\n\tUnknown2,\n"));
+    }
+
+    #[test]
+    fn test_generate_untagged_union() {
+        let schema: Schema = serde_yaml::from_str(
+            "oneOf:\n  - $ref: '#/components/schemas/Device'\n  - type: string\n  - type: integer\n",
+        )
+        .unwrap();
+        let result = generate("Target", &schema, &mut TypeContext::default()).unwrap();
+        assert!(result.contains("#[serde(untagged)]\npub enum Target {\n"));
+        assert!(result.contains("\tDevice(Device),\n\tString(String),\n\tI64(i64),\n"));
+        assert!(!result.contains("Default"));
+    }
+
+    #[test]
+    fn test_generate_tagged_union() {
+        let schema: Schema = serde_yaml::from_str(
+            "anyOf:\n  - $ref: '#/components/schemas/Device'\n  - $ref: '#/components/schemas/Site'\n\
+             \x20 - {type: object, properties: {id: {type: integer}}}\n\
+             discriminator:\n  propertyName: object_type\n  mapping:\n    dcim.device: '#/components/schemas/Device'\n",
+        )
+        .unwrap();
+        let mut ctx = TypeContext::default();
+        let result = generate("Target", &schema, &mut ctx).unwrap();
+        // The members keep the property, see `tests/generated_crates.rs` for the round trip.
+        assert_eq!(
+            result,
+            "#[derive(Serialize, Debug, Clone)]\n\
+             #[serde(untagged)]\n\
+             pub enum Target {\n\
+             \tDcimDevice(Device),\n\
+             \tSite(Site),\n\
+             }\n\
+             impl<'de> serde::Deserialize<'de> for Target {\n\
+             \tfn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {\n\
+             \t\tlet value = <serde_json::Value as serde::Deserialize>::deserialize(deserializer)?;\n\
+             \t\tmatch value.get(\"object_type\").and_then(serde_json::Value::as_str) {\n\
+             \t\t\tSome(\"dcim.device\") => serde_json::from_value(value).map(Self::DcimDevice),\n\
+             \t\t\tSome(\"Site\") => serde_json::from_value(value).map(Self::Site),\n\
+             \t\t\tSome(tag) => return Err(serde::de::Error::unknown_variant(tag, &[\"dcim.device\", \"Site\"])),\n\
+             \t\t\tNone => return Err(serde::de::Error::missing_field(\"object_type\")),\n\
+             \t\t}\n\
+             \t\t.map_err(serde::de::Error::custom)\n\
+             \t}\n\
+             }\n"
+        );
+        // The inline member has no value of the property.
+        assert_eq!(
+            ctx.warnings,
+            vec!["Member 3 of Target has no discriminator value and was skipped"]
+        );
+    }
+
+    #[test]
+    fn test_generate_union_field() {
+        let mut ctx = TypeContext::default();
+        ctx.components.schemas.insert(
+            "Target".to_owned(),
+            ReferenceOr::Item(
+                serde_yaml::from_str("oneOf: [{type: string}, {type: integer}]\n").unwrap(),
+            ),
+        );
+        let schema: Schema = serde_yaml::from_str(
+            "type: object\nrequired: [target]\nproperties:\n\
+             \x20 target: {$ref: '#/components/schemas/Target'}\n",
+        )
+        .unwrap();
+        // Unions don't implement `Default`, so structs requiring one can't either.
+        let result = generate("Cable", &schema, &mut ctx).unwrap();
+        assert!(
+            result.starts_with(
+                "#[derive(Serialize, Deserialize, Debug, Clone)]\npub struct Cable {\n"
+            )
+        );
+
+        // Unless the field is optional.
+        let result = generate("PatchedCable", &schema, &mut ctx).unwrap();
+        assert!(result.starts_with("#[derive(Serialize, Deserialize, Debug, Default, Clone)]\n"));
+    }
+
+    #[test]
+    fn test_generate_required() {
+        let schema: Schema = serde_yaml::from_str(
+            "type: object\nrequired: [id, name]\nproperties:\n\
+             \x20 id: {type: integer}\n\
+             \x20 name: {type: string, nullable: true}\n\
+             \x20 serial: {type: string}\n\
+             \x20 tenant: {type: integer, nullable: true}\n",
+        )
+        .unwrap();
+        let result = generate("Device", &schema, &mut TypeContext::default()).unwrap();
+        assert!(result.contains("\tpub id: i64,\n"));
+        assert!(result.contains("\tpub name: Option<String>,\n"));
+        assert!(result.contains(
+            "\t#[serde(default, skip_serializing_if = \"Option::is_none\")]\n\tpub serial: Option<String>,\n"
+        ));
+        assert!(result.contains(
+            "deserialize_with = \"crate::util::deserialize_some\")]\n\tpub tenant: Option<Option<i64>>,\n"
+        ));
+    }
+
+    #[test]
+    fn test_generate_workaround() {
+        let schema: Schema = serde_yaml::from_str(
+            "type: object\nrequired: [id, name, serial]\nproperties:\n\
+             \x20 id: {type: integer}\n\
+             \x20 name: {type: string}\n\
+             \x20 serial: {type: string}\n",
+        )
+        .unwrap();
+        let mut ctx = TypeContext::default();
+        ctx.workaround_mode = true;
+        ctx.unsanitary = vec![util::UnsanitaryPattern::new("Device.serial").unwrap()];
+        let result = generate("Device", &schema, &mut ctx).unwrap();
+        assert!(result.contains("\tpub id: i64,\n"));
+        assert!(result.contains("\tpub name: String,\n"));
+        assert!(result.contains("\tpub serial: Option<String>,\n"));
+
+        // Other structs are left alone.
+        let result = generate("Site", &schema, &mut ctx).unwrap();
+        assert!(result.contains("\tpub serial: String,\n"));
+    }
+
+    #[test]
+    fn test_generate_without_default() {
+        let schema: Schema = serde_yaml::from_str(
+            "type: object\nrequired: [url, id]\nproperties:\n\
+             \x20 url: {type: string, format: uri}\n\
+             \x20 id: {type: string, format: uuid}\n\
+             \x20 address: {type: string, format: ipv4}\n",
+        )
+        .unwrap();
+        let mut ctx = TypeContext::default();
+        // `url::Url` doesn't implement `Default`, and there's no sensible value to make up.
+        let result = generate("Site", &schema, &mut ctx).unwrap();
+        assert!(result.starts_with("#[derive(Serialize, Deserialize, Debug, Clone)]\n"));
+        assert!(result.contains("\tpub url: url::Url,\n\tpub id: uuid::Uuid,\n"));
+        assert!(result.contains("\tpub address: Option<std::net::IpAddr>,\n"));
+        assert!(!result.contains("impl Default"));
+
+        // Mapped types are assumed to implement it.
+        ctx.type_mappings
+            .insert("uri".to_owned(), "String".to_owned());
+        let result = generate("Site", &schema, &mut ctx).unwrap();
+        assert!(result.starts_with("#[derive(Serialize, Deserialize, Debug, Default, Clone)]\n"));
+    }
+
+    #[test]
+    fn test_generate_enum_field_default() {
+        let mut ctx = TypeContext::default();
+        ctx.components = serde_yaml::from_str(
+            "schemas:\n\
+             \x20 Status: {type: string, enum: [active, planned]}\n\
+             \x20 Kind: {type: string, enum: [a, b], default: b}\n",
+        )
+        .unwrap();
+        // Neither the inline nor the referenced enum has a default.
+        for status in [
+            "{type: string, enum: [active, planned]}",
+            "{$ref: '#/components/schemas/Status'}",
+        ] {
+            let schema: Schema = serde_yaml::from_str(&format!(
+                "type: object\nrequired: [status]\nproperties:\n  status: {}\n",
+                status
+            ))
+            .unwrap();
+            let result = generate("Device", &schema, &mut ctx).unwrap();
+            assert!(result.starts_with("#[derive(Serialize, Deserialize, Debug, Clone)]\n"));
+        }
+
+        let schema: Schema = serde_yaml::from_str(
+            "type: object\nrequired: [kind]\n\
+             properties: {kind: {$ref: '#/components/schemas/Kind'}}\n",
+        )
+        .unwrap();
+        let result = generate("Device", &schema, &mut ctx).unwrap();
+        assert!(result.starts_with("#[derive(Serialize, Deserialize, Debug, Default, Clone)]\n"));
+    }
+
+    #[test]
+    fn test_generate_array_without_items() {
+        let schema: Schema = serde_yaml::from_str(
+            "type: object\nrequired: [list]\nproperties:\n  list: {type: array}\n",
+        )
+        .unwrap();
+        let result = generate("Config", &schema, &mut TypeContext::default()).unwrap();
+        assert!(result.contains("\tpub list: Vec<serde_json::Value>,\n"));
+
+        let schema: Schema = serde_yaml::from_str("type: array\n").unwrap();
+        let result = generate("List", &schema, &mut TypeContext::default()).unwrap();
+        assert!(result.contains("pub struct List(pub serde_json::Value);\n"));
+    }
+
+    #[test]
+    fn test_generate_additional_properties() {
+        let schema: Schema = serde_yaml::from_str(
+            "type: object\nrequired: [extra]\nproperties:\n\
+             \x20 extra: {type: string}\n\
+             additionalProperties: {type: integer}\n",
+        )
+        .unwrap();
+        let result = generate("CustomFields", &schema, &mut TypeContext::default()).unwrap();
+        assert!(result.contains("\tpub extra: String,\n"));
+        assert!(result.contains(
+            "\t#[serde(flatten)]\n\tpub extra_: std::collections::HashMap<String, i64>,\n"
+        ));
+    }
+
+    #[test]
+    fn test_generate_all_of() {
+        let mut ctx = TypeContext::default();
+        ctx.components.schemas.insert(
+            "Base".to_owned(),
+            ReferenceOr::Item(
+                serde_yaml::from_str("type: object\nproperties:\n  id: {type: integer}\n").unwrap(),
+            ),
+        );
+        let schema: Schema = serde_yaml::from_str(
+            "allOf:\n  - $ref: '#/components/schemas/Base'\n\
+             \x20 - type: object\n    properties:\n      name: {type: string}\n\
+             \x20 - $ref: '#/components/schemas/Extra'\n",
+        )
+        .unwrap();
+        let result = generate("Device", &schema, &mut ctx).unwrap();
+        assert!(result.contains("pub struct Device {\n"));
+        assert!(result.contains("\tpub id: Option<i64>,\n"));
+        assert!(result.contains("\tpub name: Option<String>,\n"));
+        // Unknown components can't be merged.
+        assert!(result.contains("\t#[serde(flatten)]\n\tpub extra: Extra,\n"));
+    }
+
+    #[test]
+    fn test_generate_all_of_members() {
+        let mut ctx = TypeContext::default();
+        ctx.components.schemas.insert(
+            "Name".to_owned(),
+            ReferenceOr::Item(serde_yaml::from_str("type: string\n").unwrap()),
+        );
+        let schema: Schema = serde_yaml::from_str(
+            "allOf:\n\
+             \x20 - type: object\n    properties:\n      extra: {type: string}\n\
+             \x20 - {required: [extra], description: Only adds a requirement}\n\
+             \x20 - $ref: '#/components/schemas/Name'\n\
+             \x20 - type: integer\n\
+             \x20 - oneOf: [{$ref: '#/components/schemas/Site'}, {$ref: '#/components/schemas/Location'}]\n\
+             \x20 - $ref: '#/components/schemas/Extra'\n",
+        )
+        .unwrap();
+        let result = generate("Device", &schema, &mut ctx).unwrap();
+        assert!(result.contains("\tpub extra: String,\n"));
+        // Inline members are named after their position, and field names are kept unique.
+        assert!(result.contains("\t#[serde(flatten)]\n\tpub device_all_of_1: DeviceAllOf1,\n"));
+        assert!(result.contains("\t#[serde(flatten)]\n\tpub extra_: Extra,\n"));
+        assert!(ctx.hoisted.concat().contains("pub enum DeviceAllOf1 {\n"));
+        // Members which aren't objects can't be flattened.
+        assert!(!result.contains("pub name"));
+        assert!(!result.contains("i64"));
+        assert_eq!(
+            ctx.warnings,
+            vec![
+                "`Name` of the allOf of Device isn't an object and was skipped",
+                "An inline member of the allOf of Device isn't an object and was skipped",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_make_variant_name() {
+        assert_eq!(make_variant_name("active", 0), "Active");
+        assert_eq!(make_variant_name("dcim.device", 0), "DcimDevice");
+        assert_eq!(make_variant_name("1000base-t", 0), "V1000BaseT");
+        assert_eq!(make_variant_name("", 0), "Empty");
+        assert_eq!(make_variant_name("+", 3), "Value3");
+    }
+
     // TODO: Expand these tests.
 }
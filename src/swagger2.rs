@@ -0,0 +1,646 @@
+//! Read Swagger 2.0 documents by converting them to OpenAPI 3.0.
+//!
+//! The conversion follows the differences between both versions:
+//!
+//! - `definitions` become `components.schemas`, global `responses` become `components.responses`
+//!   and `securityDefinitions` become `components.securitySchemes`.
+//! - `host`, `basePath` and `schemes` become `servers`.
+//! - `in: body` and `in: formData` parameters become a `requestBody`, with one media type per
+//!   entry of `consumes`. Responses get one media type per entry of `produces`.
+//! - The type of every other parameter is moved into its `schema`, and its `collectionFormat` into
+//!   its `style` and `explode`. `tsv` has no style and is kept as `x-collectionFormat`.
+//! - `type: file` becomes a binary string and `x-nullable` becomes `nullable`.
+
+use serde_yaml::{Mapping, Value};
+
+use crate::error::ThanixError;
+
+/// Keys of a Swagger 2.0 parameter or header which describe its type.
+static SCHEMA_KEYS: &[&str] = &[
+    "type",
+    "format",
+    "items",
+    "default",
+    "maximum",
+    "exclusiveMaximum",
+    "minimum",
+    "exclusiveMinimum",
+    "maxLength",
+    "minLength",
+    "pattern",
+    "maxItems",
+    "minItems",
+    "uniqueItems",
+    "enum",
+    "multipleOf",
+    "x-nullable",
+];
+
+/// Check whether `doc` is a Swagger 2.0 document.
+pub fn is_swagger_2(doc: &Value) -> bool {
+    doc.get("swagger")
+        .and_then(Value::as_str)
+        .is_some_and(|v| v.starts_with("2."))
+}
+
+/// Convert a Swagger 2.0 document to OpenAPI 3.0.
+pub fn upgrade(doc: &Value) -> Result<Value, ThanixError> {
+    let swagger = doc
+        .as_mapping()
+        .ok_or_else(|| ThanixError::schema(&[], "document is not a mapping"))?;
+    let consumes = media_types(swagger.get("consumes"));
+    let produces = media_types(swagger.get("produces"));
+
+    let mut result = Mapping::new();
+    result.insert("openapi".into(), "3.0.3".into());
+    for key in ["info", "tags", "security", "externalDocs"] {
+        if let Some(value) = swagger.get(key) {
+            result.insert(key.into(), value.clone());
+        }
+    }
+    if let Some(servers) = servers(swagger) {
+        result.insert("servers".into(), servers);
+    }
+
+    let mut components = Mapping::new();
+    if let Some(Value::Mapping(definitions)) = swagger.get("definitions") {
+        components.insert("schemas".into(), Value::Mapping(definitions.clone()));
+    }
+    if let Some(Value::Mapping(responses)) = swagger.get("responses") {
+        let responses = responses
+            .iter()
+            .map(|(name, response)| (name.clone(), convert_response(response, &produces)))
+            .collect();
+        components.insert("responses".into(), Value::Mapping(responses));
+    }
+    if let Some(Value::Mapping(definitions)) = swagger.get("securityDefinitions") {
+        let schemes = definitions
+            .iter()
+            .map(|(name, scheme)| (name.clone(), convert_security_scheme(scheme)))
+            .collect();
+        components.insert("securitySchemes".into(), Value::Mapping(schemes));
+    }
+    result.insert("components".into(), Value::Mapping(components));
+
+    let mut paths = Mapping::new();
+    if let Some(Value::Mapping(items)) = swagger.get("paths") {
+        for (path, item) in items {
+            let Value::Mapping(item) = item else {
+                continue;
+            };
+            let mut converted = Mapping::new();
+            // The generators only read the parameters of operations, so the ones shared by the
+            // whole path are copied into each of them.
+            let shared = parameters(swagger, item.get("parameters"));
+            for (key, value) in item {
+                match (key.as_str(), value) {
+                    (Some("parameters"), _) => {}
+                    (Some(method), Value::Mapping(op)) if is_method(method) => {
+                        let op = convert_operation(swagger, op, &shared, (&consumes, &produces));
+                        converted.insert(key.clone(), Value::Mapping(op));
+                    }
+                    _ => {
+                        converted.insert(key.clone(), value.clone());
+                    }
+                }
+            }
+            paths.insert(path.clone(), Value::Mapping(converted));
+        }
+    }
+    result.insert("paths".into(), Value::Mapping(paths));
+
+    let mut result = Value::Mapping(result);
+    convert_schemas(&mut result);
+    Ok(result)
+}
+
+/// The server URL made of `schemes`, `host` and `basePath`.
+fn servers(swagger: &Mapping) -> Option<Value> {
+    let base_path = swagger
+        .get("basePath")
+        .and_then(Value::as_str)
+        .unwrap_or_default();
+    let url = match swagger.get("host").and_then(Value::as_str) {
+        Some(host) => {
+            let scheme = swagger
+                .get("schemes")
+                .and_then(|s| s.get(0))
+                .and_then(Value::as_str)
+                .unwrap_or("https");
+            format!("{}://{}{}", scheme, host, base_path)
+        }
+        None if !base_path.is_empty() => base_path.to_owned(),
+        None => return None,
+    };
+    let mut server = Mapping::new();
+    server.insert("url".into(), url.into());
+    Some(Value::Sequence(vec![Value::Mapping(server)]))
+}
+
+fn is_method(key: &str) -> bool {
+    matches!(
+        key,
+        "get" | "put" | "post" | "delete" | "options" | "head" | "patch"
+    )
+}
+
+/// The media types listed in `consumes` or `produces`, defaulting to JSON.
+fn media_types(value: Option<&Value>) -> Vec<String> {
+    let types: Vec<String> = value
+        .and_then(Value::as_sequence)
+        .map(|types| {
+            types
+                .iter()
+                .filter_map(|t| t.as_str().map(str::to_owned))
+                .collect()
+        })
+        .unwrap_or_default();
+    if types.is_empty() {
+        vec!["application/json".to_owned()]
+    } else {
+        types
+    }
+}
+
+/// Get the parameters listed in `value`, with references to global parameters inlined.
+fn parameters(swagger: &Mapping, value: Option<&Value>) -> Vec<Mapping> {
+    let Some(Value::Sequence(params)) = value else {
+        return Vec::new();
+    };
+    params
+        .iter()
+        .filter_map(|param| {
+            // References into other files are kept, they are resolved when bundling.
+            let param = param
+                .get("$ref")
+                .and_then(Value::as_str)
+                .and_then(|reference| reference.strip_prefix("#/parameters/"))
+                .and_then(|name| swagger.get("parameters")?.get(name))
+                .unwrap_or(param);
+            param.as_mapping().cloned()
+        })
+        .collect()
+}
+
+/// Convert an operation, adding the parameters shared by its path unless it overrides them.
+fn convert_operation(
+    swagger: &Mapping,
+    op: &Mapping,
+    shared: &[Mapping],
+    (consumes, produces): (&[String], &[String]),
+) -> Mapping {
+    let consumes = match op.get("consumes") {
+        Some(x) => media_types(Some(x)),
+        None => consumes.to_vec(),
+    };
+    let produces = match op.get("produces") {
+        Some(x) => media_types(Some(x)),
+        None => produces.to_vec(),
+    };
+
+    let mut result = Mapping::new();
+    let mut params = Vec::new();
+    let mut body = None;
+    let mut form = Vec::new();
+    let own = parameters(swagger, op.get("parameters"));
+    let key = |param: &Mapping| (param.get("name").cloned(), param.get("in").cloned());
+    let inherited = shared
+        .iter()
+        .filter(|param| !own.iter().any(|p| key(p) == key(param)))
+        .cloned();
+    for param in inherited.chain(own.iter().cloned()) {
+        match param.get("in").and_then(Value::as_str) {
+            Some("body") => body = Some(param),
+            Some("formData") => form.push(param),
+            _ => params.push(convert_parameter(&param)),
+        }
+    }
+
+    for (key, value) in op {
+        match key.as_str() {
+            Some("consumes" | "produces" | "parameters") => {}
+            Some("responses") => {
+                let responses = value
+                    .as_mapping()
+                    .map(|responses| {
+                        responses
+                            .iter()
+                            .map(|(status, r)| (status.clone(), convert_response(r, &produces)))
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                result.insert(key.clone(), Value::Mapping(responses));
+            }
+            _ => {
+                result.insert(key.clone(), value.clone());
+            }
+        }
+    }
+    if !params.is_empty() {
+        result.insert("parameters".into(), Value::Sequence(params));
+    }
+    if let Some(body) = body {
+        result.insert("requestBody".into(), body_request(&body, &consumes));
+    } else if !form.is_empty() {
+        result.insert("requestBody".into(), form_request(&form, &consumes));
+    }
+    result
+}
+
+/// Convert a non-body parameter, moving its type into a `schema`.
+fn convert_parameter(param: &Mapping) -> Value {
+    if param.contains_key("$ref") {
+        return Value::Mapping(param.clone());
+    }
+    let mut result = Mapping::new();
+    let mut schema = Mapping::new();
+    for (key, value) in param {
+        match key.as_str() {
+            Some(k) if SCHEMA_KEYS.contains(&k) => {
+                schema.insert(key.clone(), value.clone());
+            }
+            Some("collectionFormat") => match value.as_str() {
+                Some("multi") => {
+                    result.insert("style".into(), "form".into());
+                    result.insert("explode".into(), true.into());
+                }
+                // `csv` is the default of both versions.
+                Some("csv") => {
+                    result.insert("explode".into(), false.into());
+                }
+                Some("ssv") => {
+                    result.insert("style".into(), "spaceDelimited".into());
+                    result.insert("explode".into(), false.into());
+                }
+                Some("pipes") => {
+                    result.insert("style".into(), "pipeDelimited".into());
+                    result.insert("explode".into(), false.into());
+                }
+                // OpenAPI 3.0 has no style for tab-delimited values.
+                _ => {
+                    result.insert("x-collectionFormat".into(), value.clone());
+                }
+            },
+            _ => {
+                result.insert(key.clone(), value.clone());
+            }
+        }
+    }
+    result.insert("schema".into(), Value::Mapping(schema));
+    Value::Mapping(result)
+}
+
+/// The `requestBody` of an `in: body` parameter.
+fn body_request(param: &Mapping, consumes: &[String]) -> Value {
+    let schema = param.get("schema").cloned().unwrap_or_default();
+    request_body(
+        param.get("description"),
+        param.get("required"),
+        schema,
+        consumes,
+    )
+}
+
+/// The `requestBody` made of all `in: formData` parameters.
+fn form_request(params: &[Mapping], consumes: &[String]) -> Value {
+    let mut properties = Mapping::new();
+    let mut required = Vec::new();
+    let mut has_file = false;
+    for param in params {
+        let Some(name) = param.get("name").cloned() else {
+            continue;
+        };
+        let mut schema = Mapping::new();
+        for (key, value) in param {
+            if key
+                .as_str()
+                .is_some_and(|k| SCHEMA_KEYS.contains(&k) || k == "description")
+            {
+                schema.insert(key.clone(), value.clone());
+            }
+        }
+        has_file |= schema.get("type").and_then(Value::as_str) == Some("file");
+        if param.get("required").and_then(Value::as_bool) == Some(true) {
+            required.push(name.clone());
+        }
+        properties.insert(name, Value::Mapping(schema));
+    }
+
+    let mut schema = Mapping::new();
+    schema.insert("type".into(), "object".into());
+    schema.insert("properties".into(), Value::Mapping(properties));
+    if !required.is_empty() {
+        schema.insert("required".into(), Value::Sequence(required));
+    }
+    // Forms are only sent as forms, whatever else the operation consumes.
+    let form_types: Vec<String> = consumes
+        .iter()
+        .filter(|t| t.starts_with("multipart/") || *t == "application/x-www-form-urlencoded")
+        .cloned()
+        .collect();
+    let form_types = if !form_types.is_empty() {
+        form_types
+    } else if has_file {
+        vec!["multipart/form-data".to_owned()]
+    } else {
+        vec!["application/x-www-form-urlencoded".to_owned()]
+    };
+    request_body(
+        None,
+        Some(&true.into()),
+        Value::Mapping(schema),
+        &form_types,
+    )
+}
+
+fn request_body(
+    description: Option<&Value>,
+    required: Option<&Value>,
+    schema: Value,
+    media_types: &[String],
+) -> Value {
+    let mut result = Mapping::new();
+    if let Some(description) = description {
+        result.insert("description".into(), description.clone());
+    }
+    if let Some(required) = required {
+        result.insert("required".into(), required.clone());
+    }
+    result.insert("content".into(), content(&schema, media_types));
+    Value::Mapping(result)
+}
+
+/// A `content` map with `schema` for all `media_types`.
+fn content(schema: &Value, media_types: &[String]) -> Value {
+    let mut content = Mapping::new();
+    for media_type in media_types {
+        let mut media = Mapping::new();
+        media.insert("schema".into(), schema.clone());
+        content.insert(media_type.as_str().into(), Value::Mapping(media));
+    }
+    Value::Mapping(content)
+}
+
+/// Convert a response, or keep a reference to a global one.
+fn convert_response(response: &Value, produces: &[String]) -> Value {
+    let Value::Mapping(response) = response else {
+        return response.clone();
+    };
+    if response.contains_key("$ref") {
+        return Value::Mapping(response.clone());
+    }
+
+    let mut result = Mapping::new();
+    result.insert(
+        "description".into(),
+        response
+            .get("description")
+            .cloned()
+            .unwrap_or_else(|| "".into()),
+    );
+    if let Some(schema) = response.get("schema") {
+        result.insert("content".into(), content(schema, produces));
+    }
+    if let Some(Value::Mapping(headers)) = response.get("headers") {
+        let headers = headers
+            .iter()
+            .map(|(name, header)| {
+                let mut converted = Mapping::new();
+                let mut schema = Mapping::new();
+                for (key, value) in header.as_mapping().into_iter().flatten() {
+                    if key.as_str() == Some("description") {
+                        converted.insert(key.clone(), value.clone());
+                    } else if key.as_str().is_some_and(|k| SCHEMA_KEYS.contains(&k)) {
+                        schema.insert(key.clone(), value.clone());
+                    }
+                }
+                converted.insert("schema".into(), Value::Mapping(schema));
+                (name.clone(), Value::Mapping(converted))
+            })
+            .collect();
+        result.insert("headers".into(), Value::Mapping(headers));
+    }
+    Value::Mapping(result)
+}
+
+/// Convert an entry of `securityDefinitions` to a security scheme.
+fn convert_security_scheme(scheme: &Value) -> Value {
+    let Value::Mapping(scheme) = scheme else {
+        return scheme.clone();
+    };
+    let mut result = Mapping::new();
+    if let Some(description) = scheme.get("description") {
+        result.insert("description".into(), description.clone());
+    }
+    match scheme.get("type").and_then(Value::as_str) {
+        Some("basic") => {
+            result.insert("type".into(), "http".into());
+            result.insert("scheme".into(), "basic".into());
+        }
+        Some("oauth2") => {
+            result.insert("type".into(), "oauth2".into());
+            let flow = match scheme.get("flow").and_then(Value::as_str) {
+                Some("implicit") => "implicit",
+                Some("password") => "password",
+                Some("application") => "clientCredentials",
+                _ => "authorizationCode",
+            };
+            let mut converted = Mapping::new();
+            for key in ["authorizationUrl", "tokenUrl", "scopes"] {
+                if let Some(value) = scheme.get(key) {
+                    converted.insert(key.into(), value.clone());
+                }
+            }
+            if !converted.contains_key("scopes") {
+                converted.insert("scopes".into(), Value::Mapping(Mapping::new()));
+            }
+            let mut flows = Mapping::new();
+            flows.insert(flow.into(), Value::Mapping(converted));
+            result.insert("flows".into(), Value::Mapping(flows));
+        }
+        // `apiKey` is the same in both versions.
+        _ => {
+            for (key, value) in scheme {
+                result.insert(key.clone(), value.clone());
+            }
+        }
+    }
+    Value::Mapping(result)
+}
+
+/// Rewrite references and Swagger-only keywords of all schemas within `value`.
+fn convert_schemas(value: &mut Value) {
+    match value {
+        Value::Mapping(map) => {
+            if let Some(Value::String(reference)) = map.get_mut("$ref") {
+                for (old, new) in [
+                    ("#/definitions/", "#/components/schemas/"),
+                    ("#/responses/", "#/components/responses/"),
+                ] {
+                    if let Some(rest) = reference.strip_prefix(old) {
+                        *reference = format!("{}{}", new, rest);
+                    }
+                }
+            }
+            if map.get("type").and_then(Value::as_str) == Some("file") {
+                map.insert("type".into(), "string".into());
+                map.insert("format".into(), "binary".into());
+            }
+            if let Some(nullable) = map.remove("x-nullable") {
+                map.insert("nullable".into(), nullable);
+            }
+            if let Some(Value::String(property)) = map.get("discriminator") {
+                let mut discriminator = Mapping::new();
+                discriminator.insert("propertyName".into(), property.as_str().into());
+                map.insert("discriminator".into(), Value::Mapping(discriminator));
+            }
+            for (_, v) in map.iter_mut() {
+                convert_schemas(v);
+            }
+        }
+        Value::Sequence(items) => {
+            for v in items {
+                convert_schemas(v);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use openapiv3::OpenAPI;
+
+    use super::*;
+
+    static SPEC: &str = "swagger: '2.0'\n\
+        info: {title: Legacy, version: '1'}\n\
+        host: appliance.example.com\n\
+        basePath: /api\n\
+        schemes: [http]\n\
+        consumes: [application/json]\n\
+        produces: [application/json]\n\
+        securityDefinitions:\n\
+        \x20 token: {type: apiKey, name: Authorization, in: header}\n\
+        \x20 basic: {type: basic}\n\
+        parameters:\n\
+        \x20 limit: {name: limit, in: query, type: integer}\n\
+        paths:\n\
+        \x20 /things/:\n\
+        \x20   get:\n\
+        \x20     operationId: things_list\n\
+        \x20     parameters:\n\
+        \x20       - $ref: '#/parameters/limit'\n\
+        \x20       - {name: tag, in: query, type: array, items: {type: string}, collectionFormat: multi}\n\
+        \x20       - {name: id, in: query, type: array, items: {type: integer}, collectionFormat: pipes}\n\
+        \x20       - {name: q, in: query, type: array, items: {type: string}, collectionFormat: tsv}\n\
+        \x20     responses:\n\
+        \x20       '200':\n\
+        \x20         description: OK\n\
+        \x20         schema: {type: array, items: {$ref: '#/definitions/Thing'}}\n\
+        \x20   post:\n\
+        \x20     operationId: things_create\n\
+        \x20     parameters:\n\
+        \x20       - {name: body, in: body, required: true, schema: {$ref: '#/definitions/Thing'}}\n\
+        \x20     responses:\n\
+        \x20       '201': {description: Created, schema: {$ref: '#/definitions/Thing'}}\n\
+        \x20 /things/{id}/:\n\
+        \x20   parameters:\n\
+        \x20     - {name: id, in: path, required: true, type: integer}\n\
+        \x20   get:\n\
+        \x20     operationId: things_read\n\
+        \x20     responses: {'200': {description: OK, schema: {$ref: '#/definitions/Thing'}}}\n\
+        \x20 /things/upload/:\n\
+        \x20   post:\n\
+        \x20     operationId: things_upload\n\
+        \x20     consumes: [multipart/form-data]\n\
+        \x20     parameters:\n\
+        \x20       - {name: file, in: formData, type: file, required: true}\n\
+        \x20       - {name: comment, in: formData, type: string}\n\
+        \x20     responses:\n\
+        \x20       '204': {description: Uploaded}\n\
+        definitions:\n\
+        \x20 Thing:\n\
+        \x20   type: object\n\
+        \x20   properties:\n\
+        \x20     name: {type: string, x-nullable: true}\n";
+
+    fn upgraded() -> Value {
+        upgrade(&serde_yaml::from_str(SPEC).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_upgrade_parses() {
+        let api: OpenAPI = serde_yaml::from_value(upgraded()).unwrap();
+        assert_eq!(api.openapi, "3.0.3");
+        assert_eq!(api.servers[0].url, "http://appliance.example.com/api");
+        assert_eq!(api.paths.paths.len(), 3);
+        let components = api.components.unwrap();
+        assert!(components.schemas.contains_key("Thing"));
+        assert_eq!(components.security_schemes.len(), 2);
+    }
+
+    #[test]
+    fn test_upgrade_parameters() {
+        let doc = upgraded();
+        let get = &doc["paths"]["/things/"]["get"];
+        assert_eq!(get["parameters"][0]["name"], "limit");
+        assert_eq!(get["parameters"][0]["schema"]["type"], "integer");
+        assert_eq!(get["parameters"][1]["schema"]["items"]["type"], "string");
+        assert_eq!(get["parameters"][1]["explode"], true);
+        assert_eq!(get["parameters"][2]["style"], "pipeDelimited");
+        assert_eq!(get["parameters"][2]["explode"], false);
+        assert_eq!(get["parameters"][3]["x-collectionFormat"], "tsv");
+        assert_eq!(
+            get["responses"]["200"]["content"]["application/json"]["schema"]["items"]["$ref"],
+            "#/components/schemas/Thing"
+        );
+    }
+
+    #[test]
+    fn test_upgrade_path_parameters() {
+        let doc = upgraded();
+        let item = &doc["paths"]["/things/{id}/"];
+        assert!(item.get("parameters").is_none());
+        assert_eq!(item["get"]["parameters"][0]["name"], "id");
+        assert_eq!(item["get"]["parameters"][0]["schema"]["type"], "integer");
+    }
+
+    #[test]
+    fn test_upgrade_body() {
+        let doc = upgraded();
+        let body = &doc["paths"]["/things/"]["post"]["requestBody"];
+        assert_eq!(body["required"], true);
+        assert_eq!(
+            body["content"]["application/json"]["schema"]["$ref"],
+            "#/components/schemas/Thing"
+        );
+
+        let form = &doc["paths"]["/things/upload/"]["post"]["requestBody"]["content"]["multipart/form-data"]
+            ["schema"];
+        assert_eq!(form["properties"]["file"]["type"], "string");
+        assert_eq!(form["properties"]["file"]["format"], "binary");
+        assert_eq!(form["required"][0], "file");
+    }
+
+    #[test]
+    fn test_upgrade_schemas() {
+        let doc = upgraded();
+        let thing = &doc["components"]["schemas"]["Thing"];
+        assert_eq!(thing["properties"]["name"]["nullable"], true);
+        let schemes = &doc["components"]["securitySchemes"];
+        assert_eq!(schemes["token"]["type"], "apiKey");
+        assert_eq!(schemes["basic"]["type"], "http");
+        assert_eq!(schemes["basic"]["scheme"], "basic");
+    }
+
+    #[test]
+    fn test_is_swagger_2() {
+        assert!(is_swagger_2(
+            &serde_yaml::from_str("swagger: '2.0'").unwrap()
+        ));
+        assert!(!is_swagger_2(
+            &serde_yaml::from_str("openapi: 3.0.3").unwrap()
+        ));
+    }
+}
@@ -1,6 +1,8 @@
 //! Various supporting functionality.
 
-/// Names of NetBox API objects which can cause the client to crash at response data serialization.
+use glob::{MatchOptions, Pattern, PatternError};
+
+/// Patterns of NetBox API objects which can cause the client to crash at response data serialization.
 ///
 /// This workaround is necessary as a lot of the time, some database entries can have fieds set to
 /// `null` even though the API schema states that they are not nullable.
@@ -9,59 +11,163 @@
 /// required structs.
 ///
 /// To work around this, the `--workaround` flag was added to Thanix, which will check at struct
-/// generation, whether the struct is part of this **manually maintained list of troublemakers**
+/// generation, whether the struct matches one of these patterns. It is used if no `--unsanitary`
+/// patterns are configured, and loosens every response struct, as it always has.
 ///
-/// ** This list is maintained manually by the Nazara Team, as there is currently no real way to
-/// automate this.
-/// If you have problems and need something to be added to it, please open a bug in our [issues
+/// ** For your own NetBox instance, you can narrow this down with `--unsanitary`, or derive exact
+/// patterns from recorded responses with `thanix infer-overrides`.
+/// If you have problems with the workaround, please open a bug in our [issues
 /// section](https://github.com/The-Nazara-Project/Thanix/issues/).**
-static UNSANITARY_OBJECTS: &[&str] = &["interface"];
+pub static UNSANITARY_OBJECTS: &[&str] = &["*"];
+
+/// A schema, or a single field of it, which is loosened in workaround mode.
+///
+/// Written as `Schema` or `Schema.field`, where both parts are matched against the whole name,
+/// ignoring case, unless they contain glob characters like `*`, e.g. `*Interface*` or
+/// `Device.primary_ip*`.
+///
+/// Unlike the former list of names, a pattern doesn't match names merely containing it, so
+/// `interface` has to be written as `*interface*` to also match `VMInterface`.
+#[derive(Debug, Clone)]
+pub struct UnsanitaryPattern {
+    schema: Pattern,
+    field: Option<Pattern>,
+}
+
+impl UnsanitaryPattern {
+    /// Parse a `Schema` or `Schema.field` pattern.
+    pub fn new(pattern: &str) -> Result<Self, PatternError> {
+        let pattern = pattern.trim();
+        let (schema, field) = match pattern.split_once('.') {
+            Some((schema, field)) => (schema, Some(Pattern::new(field)?)),
+            None => (pattern, None),
+        };
+        Ok(Self {
+            schema: Pattern::new(schema)?,
+            field,
+        })
+    }
+}
 
-/// Check if a given struct's name contains any entry from the `UNSANITARY_OBJECTS` list.
+/// Check if the field `field` of the struct `schema` matches any of `patterns`.
+///
+/// A pattern naming only the schema matches all of its fields but `id`, as an object always has
+/// one. To loosen `id` as well, name it explicitly, e.g. `Interface.id`.
 ///
 /// # Parameters
-/// * `name: &str` - Struct name to check
+/// * `patterns: &[UnsanitaryPattern]` - The configured patterns
+/// * `schema: &str` - Struct name to check
+/// * `field: &str` - Field name to check, as written in the schema
 ///
 /// # Returns
 ///
 /// `true/false` depending if a match is found.
-pub fn is_unsanitary(name: &str) -> bool {
-    UNSANITARY_OBJECTS
-        .iter()
-        .any(|&word| name.to_lowercase().contains(word))
+pub fn is_unsanitary(patterns: &[UnsanitaryPattern], schema: &str, field: &str) -> bool {
+    let options = MatchOptions {
+        case_sensitive: false,
+        ..MatchOptions::new()
+    };
+    patterns.iter().any(|pattern| {
+        pattern.schema.matches_with(schema, options)
+            && match &pattern.field {
+                Some(f) => f.matches_with(field, options),
+                None => field != "id",
+            }
+    })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn patterns(patterns: &[&str]) -> Vec<UnsanitaryPattern> {
+        patterns
+            .iter()
+            .map(|p| UnsanitaryPattern::new(p).unwrap())
+            .collect()
+    }
+
     #[test]
     fn test_exact_match() {
-        assert!(is_unsanitary("interface"));
+        let patterns = patterns(&["Interface"]);
+        assert!(is_unsanitary(&patterns, "Interface", "name"));
+        assert!(!is_unsanitary(&patterns, "VMInterface", "name"));
     }
 
     #[test]
     fn test_case_insensitive() {
-        assert!(is_unsanitary("INTERFACE"));
-        assert!(is_unsanitary("interface"));
+        let patterns = patterns(&["interface.Name"]);
+        assert!(is_unsanitary(&patterns, "INTERFACE", "name"));
+        assert!(is_unsanitary(&patterns, "Interface", "NAME"));
     }
 
     #[test]
     fn test_substring_match() {
-        assert!(is_unsanitary("interface_type"));
-        assert!(is_unsanitary("interface_name"));
+        // Names merely containing a pattern aren't matched, that takes a glob.
+        assert!(!is_unsanitary(
+            &patterns(&["interface"]),
+            "InterfaceType",
+            "name"
+        ));
+        let patterns = patterns(&["*interface*"]);
+        assert!(is_unsanitary(&patterns, "InterfaceType", "name"));
+        assert!(is_unsanitary(&patterns, "VMInterfaceName", "name"));
     }
 
     #[test]
     fn test_no_match() {
-        assert!(!is_unsanitary("device"));
-        assert!(!is_unsanitary(""));
-        assert!(!is_unsanitary("other"));
+        let patterns = patterns(&["*interface*"]);
+        assert!(!is_unsanitary(&patterns, "Device", "name"));
+        assert!(!is_unsanitary(&patterns, "", "name"));
+        assert!(!is_unsanitary(&patterns, "Other", "name"));
     }
 
     #[test]
     fn test_whitespace() {
-        assert!(is_unsanitary(" interface "));
-        assert!(is_unsanitary("\tinterface\n"));
+        let patterns = patterns(&[" Interface ", "\tDevice.name\n"]);
+        assert!(is_unsanitary(&patterns, "Interface", "name"));
+        assert!(is_unsanitary(&patterns, "Device", "name"));
+    }
+
+    #[test]
+    fn test_default_match() {
+        let patterns = patterns(UNSANITARY_OBJECTS);
+        assert!(is_unsanitary(&patterns, "Interface", "name"));
+        assert!(is_unsanitary(&patterns, "Device", "name"));
+        assert!(!is_unsanitary(&patterns, "Device", "id"));
+    }
+
+    #[test]
+    fn test_glob_match() {
+        let patterns = patterns(&["*Interface*"]);
+        assert!(is_unsanitary(&patterns, "Interface", "name"));
+        assert!(is_unsanitary(&patterns, "VMInterface", "name"));
+        assert!(is_unsanitary(&patterns, "InterfaceTemplate", "name"));
+        assert!(!is_unsanitary(&patterns, "Device", "name"));
+    }
+
+    #[test]
+    fn test_field_match() {
+        let patterns = patterns(&["Device.primary_ip*", "Interface.id"]);
+        assert!(is_unsanitary(&patterns, "Device", "primary_ip4"));
+        assert!(!is_unsanitary(&patterns, "Device", "name"));
+        assert!(is_unsanitary(&patterns, "Interface", "id"));
+        assert!(!is_unsanitary(&patterns, "Interface", "name"));
+    }
+
+    #[test]
+    fn test_id_excluded() {
+        let patterns = patterns(&["Interface"]);
+        assert!(!is_unsanitary(&patterns, "Interface", "id"));
+    }
+
+    #[test]
+    fn test_no_patterns() {
+        assert!(!is_unsanitary(&[], "Interface", "name"));
+    }
+
+    #[test]
+    fn test_invalid_pattern() {
+        assert!(UnsanitaryPattern::new("Interface.[").is_err());
     }
 }
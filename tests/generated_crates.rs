@@ -0,0 +1,270 @@
+//! Build generated crates and run tests against them.
+//!
+//! These tests compile the generated code with its dependencies, so they are ignored by default.
+//! Run them with `cargo test -- --ignored`.
+
+use std::path::PathBuf;
+use std::process::Command;
+
+use openapiv3::OpenAPI;
+use thanix::{GeneratedCrate, Generator, GeneratorConfig};
+
+/// Write `output` to its own directory below the target directory, add `tests` as the test file
+/// of the generated crate and run `cargo test` for it.
+fn run_tests(name: &str, mut output: GeneratedCrate, tests: &str) {
+    let target = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("target");
+    let dir = target.join("generated-crates").join(name);
+    if dir.exists() {
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+    output
+        .files
+        .insert("tests/generated.rs".into(), tests.to_owned());
+    output.write(&dir).unwrap();
+
+    let status = Command::new(env!("CARGO"))
+        .arg("test")
+        .arg("--manifest-path")
+        .arg(dir.join("Cargo.toml"))
+        // Share the dependencies between all generated crates.
+        .env("CARGO_TARGET_DIR", target.join("generated-crates-target"))
+        .status()
+        .unwrap();
+    assert!(
+        status.success(),
+        "tests of the generated crate {} failed",
+        name
+    );
+}
+
+#[test]
+#[ignore = "builds a generated crate"]
+fn test_discriminated_union() {
+    let spec = "openapi: 3.0.3\n\
+        info: {title: Test, version: '1'}\n\
+        paths: {}\n\
+        components:\n\
+        \x20 schemas:\n\
+        \x20   Pet:\n\
+        \x20     oneOf:\n\
+        \x20       - $ref: '#/components/schemas/Cat'\n\
+        \x20       - $ref: '#/components/schemas/Dog'\n\
+        \x20     discriminator:\n\
+        \x20       propertyName: pet_type\n\
+        \x20       mapping: {cat: '#/components/schemas/Cat'}\n\
+        \x20   Cat:\n\
+        \x20     type: object\n\
+        \x20     required: [pet_type, meow]\n\
+        \x20     properties: {pet_type: {type: string}, meow: {type: boolean}}\n\
+        \x20   Dog:\n\
+        \x20     type: object\n\
+        \x20     required: [pet_type, meow]\n\
+        \x20     properties: {pet_type: {type: string}, meow: {type: boolean}}\n";
+    let api: OpenAPI = serde_yaml::from_str(spec).unwrap();
+    let output = Generator::new(GeneratorConfig::default())
+        .generate(&api)
+        .unwrap();
+    run_tests(
+        "discriminated_union",
+        output,
+        r##"
+use thanix_client::types::{Cat, Dog, Pet};
+
+#[test]
+fn round_trip() {
+    for json in [
+        r#"{"pet_type":"cat","meow":true}"#,
+        r#"{"pet_type":"Dog","meow":false}"#,
+    ] {
+        let pet: Pet = serde_json::from_str(json).unwrap();
+        assert_eq!(serde_json::to_string(&pet).unwrap(), json);
+    }
+    // Both members have the same fields, so only the tag tells them apart.
+    let pet: Pet = serde_json::from_str(r#"{"meow":false,"pet_type":"Dog"}"#).unwrap();
+    assert!(matches!(pet, Pet::Dog(Dog { meow: false, .. })));
+    let pet = Pet::Cat(Cat { pet_type: "cat".to_owned(), meow: true });
+    assert_eq!(serde_json::to_value(&pet).unwrap()["pet_type"], "cat");
+}
+
+#[test]
+fn invalid_tag() {
+    let err = serde_json::from_str::<Pet>(r#"{"meow":true,"pet_type":"Bird"}"#).unwrap_err();
+    assert!(err.to_string().contains("unknown variant `Bird`"));
+    let err = serde_json::from_str::<Pet>(r#"{"meow":true}"#).unwrap_err();
+    assert!(err.to_string().contains("missing field `pet_type`"));
+}
+"##,
+    );
+}
+
+#[test]
+#[ignore = "builds a generated crate"]
+fn test_reserved_module_name() {
+    let spec = "openapi: 3.0.3\n\
+        info: {title: Test, version: '1'}\n\
+        paths:\n\
+        \x20 /api/auth/login/:\n\
+        \x20   post: {tags: [auth], operationId: auth_login, responses: {'204': {description: OK}}}\n";
+    let api: OpenAPI = serde_yaml::from_str(spec).unwrap();
+    let output = Generator::new(GeneratorConfig::default())
+        .generate(&api)
+        .unwrap();
+    run_tests(
+        "reserved_module_name",
+        output,
+        r#"
+use thanix_client::auth::Auth;
+use thanix_client::util::ThanixClient;
+
+#[test]
+fn auth_module() {
+    let client = ThanixClient::builder("http://localhost").build().unwrap();
+    // The module is renamed, as the client has an `auth` method already.
+    let _ = client.auth_api();
+    assert!(matches!(client.auth(), Auth::None));
+}
+"#,
+    );
+}
+
+#[test]
+#[ignore = "builds a generated crate"]
+fn test_client_builder() {
+    let spec = "openapi: 3.0.3\n\
+        info: {title: Test, version: '1'}\n\
+        paths: {}\n";
+    let api: OpenAPI = serde_yaml::from_str(spec).unwrap();
+    let output = Generator::new(GeneratorConfig::default())
+        .generate(&api)
+        .unwrap();
+    run_tests(
+        "client_builder",
+        output,
+        r#"
+use thanix_client::auth::Auth;
+use thanix_client::util::{BuildError, ThanixClient};
+
+fn base_url(url: &str) -> Result<String, BuildError> {
+    ThanixClient::builder(url).build().map(|client| client.base_url)
+}
+
+#[test]
+fn normalize_base_url() {
+    assert_eq!(base_url("https://netbox.example.com").unwrap(), "https://netbox.example.com");
+    assert_eq!(base_url(" https://netbox.example.com// ").unwrap(), "https://netbox.example.com");
+    assert_eq!(base_url("http://localhost:8000/netbox/").unwrap(), "http://localhost:8000/netbox");
+}
+
+#[test]
+fn invalid_base_url() {
+    for url in [
+        "netbox.example.com",
+        "ftp://netbox.example.com",
+        "https://netbox.example.com/?limit=1",
+        "https://netbox.example.com/#top",
+    ] {
+        match base_url(url) {
+            Err(BuildError::InvalidUrl(invalid, _)) => assert_eq!(invalid, url),
+            other => panic!("{} was accepted: {:?}", url, other.map(|_| ())),
+        }
+    }
+    let err = base_url("ftp://netbox.example.com").unwrap_err();
+    assert!(err.to_string().contains("the scheme has to be `http` or `https`"));
+}
+
+#[test]
+fn invalid_header() {
+    for (name, value) in [("X Source", "inventory"), ("X-Source", "line\nbreak")] {
+        let result = ThanixClient::builder("http://localhost").header(name, value).build();
+        assert!(matches!(result, Err(BuildError::InvalidHeader(header)) if header == name));
+    }
+    ThanixClient::builder("http://localhost")
+        .header("X-Source", "inventory")
+        .build()
+        .unwrap();
+}
+
+#[test]
+fn missing_ca_bundle() {
+    let path = "/nonexistent/netbox-ca.pem";
+    let result = ThanixClient::builder("http://localhost").ca_bundle(path).build();
+    let Err(err @ BuildError::CaBundle(..)) = result else {
+        panic!("the missing CA bundle was accepted");
+    };
+    assert!(err.to_string().starts_with("Unable to read CA bundle /nonexistent/netbox-ca.pem: "));
+    assert!(std::error::Error::source(&err).is_some());
+}
+
+#[test]
+fn new_client() {
+    let client = ThanixClient::new(
+        reqwest::blocking::Client::new(),
+        "http://localhost/",
+        Auth::Token("token".to_owned()),
+    );
+    assert_eq!(client.base_url, "http://localhost");
+    assert_eq!(client.auth(), Auth::Token("token".to_owned()));
+}
+"#,
+    );
+}
+
+#[test]
+#[ignore = "builds a generated crate"]
+fn test_auth_apply() {
+    let spec = "openapi: 3.0.3\n\
+        info: {title: Test, version: '1'}\n\
+        security: [{cookieAuth: []}]\n\
+        paths: {}\n\
+        components:\n\
+        \x20 securitySchemes:\n\
+        \x20   cookieAuth: {type: apiKey, in: cookie, name: sessionid}\n";
+    let api: OpenAPI = serde_yaml::from_str(spec).unwrap();
+    let output = Generator::new(GeneratorConfig::default())
+        .generate(&api)
+        .unwrap();
+    run_tests(
+        "auth_apply",
+        output,
+        r#"
+use thanix_client::auth::{Auth, COOKIE_AUTH};
+
+#[test]
+fn single_cookie_header() {
+    let request = reqwest::blocking::Client::new().get("http://localhost/");
+    let request = Auth::ApiKey("key".to_owned())
+        .apply(request, Some(&[&[COOKIE_AUTH]]), Some("csrftoken=token".to_owned()))
+        .build()
+        .unwrap();
+    let cookies: Vec<_> = request.headers().get_all("Cookie").iter().collect();
+    assert_eq!(cookies, ["csrftoken=token; sessionid=key"]);
+}
+
+#[test]
+fn without_security_schemes() {
+    let client = reqwest::blocking::Client::new();
+    // Only tokens and basic credentials fall back to `Authorization`.
+    let request = Auth::Token("token".to_owned())
+        .apply(client.get("http://localhost/"), None, None)
+        .build()
+        .unwrap();
+    assert_eq!(request.headers()["Authorization"], "Token token");
+    let request = Auth::ApiKey("key".to_owned())
+        .apply(client.get("http://localhost/"), None, None)
+        .build()
+        .unwrap();
+    assert!(request.headers().is_empty());
+}
+
+#[test]
+fn unsatisfied_requirement() {
+    let request = reqwest::blocking::Client::new().get("http://localhost/");
+    let request = Auth::Basic { username: "user".to_owned(), password: None }
+        .apply(request, Some(&[&[COOKIE_AUTH]]), None)
+        .build()
+        .unwrap();
+    assert!(request.headers().is_empty());
+}
+"#,
+    );
+}